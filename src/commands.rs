@@ -1,8 +1,14 @@
+pub mod armies;
 pub mod build;
+pub mod claim;
+pub mod demolish;
 pub mod dev;
+pub mod diplomacy;
 pub mod faction;
 pub mod help;
 pub mod map;
 pub mod r#move;
 pub mod tile;
+pub mod train;
+pub mod upgrade;
 pub mod user;
\ No newline at end of file
@@ -0,0 +1,55 @@
+use crate::combat::format_units;
+use crate::misc::log_command_used;
+use crate::{db, Context, Error};
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "List the armies your faction has on the march")
+)]
+pub(crate) async fn armies(ctx: Context<'_>) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(());
+    }
+    let armies = db::armies::get_all_by_faction(user.faction.clone()).await?;
+    let mut list = "".to_string();
+    for (index, army) in armies.iter().enumerate() {
+        let (x, y) = army.position();
+        let (to_x, to_y) = army.destination();
+        let heading = if army.returning {
+            "heading home to"
+        } else {
+            "marching to"
+        };
+        list.push_str(&format!(
+            "**{}.** At {}, {}, {} {}, {} - arrives <t:{}:R>\n{}",
+            index + 1,
+            x,
+            y,
+            heading,
+            to_x,
+            to_y,
+            army.arrives_at,
+            format_units(&army.units)
+        ));
+    }
+    if list.is_empty() {
+        list = "No armies are on the march".to_string();
+    }
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Armies of {}", user.faction))
+                .description(list)
+        })
+    })
+    .await?;
+    Ok(())
+}
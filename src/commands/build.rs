@@ -1,15 +1,25 @@
+use std::time::SystemTime;
+
+use rand::Rng;
+
 use crate::conversions::string_to_building;
 use crate::misc::log_command_used;
-use crate::types::buildings::Building;
+use crate::types::buildings::{Building, ConstructionJob};
+use crate::types::diplomacy::Stance;
 use crate::types::permissions::Permissions;
 use crate::{db, Context, Error};
 
+#[poise::command(slash_command, subcommands("new", "queue", "cancel"))]
+pub(crate) async fn build(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
 #[poise::command(
 slash_command,
 // ephemeral,
-description_localized("en-US", "Construct a building")
+description_localized("en-US", "Start constructing a building")
 )]
-pub(crate) async fn build(
+pub(crate) async fn new(
     ctx: Context<'_>,
     #[description_localized("en-US", "The building to build")] building: String,
     #[description_localized("en-US", "The amount of buildings to build")] amount: i32,
@@ -37,20 +47,27 @@ pub(crate) async fn build(
             .unwrap();
         return Ok(());
     }
-    // Check that the tile isn't already owned by someone else
+    // Check that the tile is ours. Anything built on an unowned tile would be abandoned before it
+    // was finished
     let tile_exists = db::tiles::check_tile(x, y).await.unwrap();
-    if tile_exists {
-        let tile = db::tiles::get_tile(x, y).await.unwrap();
-        let faction = db::users::get_user(ctx.author().id.to_string())
-            .await
-            .unwrap()
-            .faction;
-        if !tile.faction.is_empty() && tile.faction != faction {
+    let tile_faction = match tile_exists {
+        true => db::tiles::get_tile(x, y).await.unwrap().faction,
+        false => "".to_string(),
+    };
+    if tile_faction != user.faction {
+        if !tile_faction.is_empty()
+            && db::relations::get_stance(user.faction.clone(), tile_faction).await?
+                == Stance::Alliance
+        {
+            ctx.say("That tile belongs to your ally! You can't build on it.")
+                .await
+                .unwrap();
+        } else {
             ctx.say("You can't build on tiles that aren't yours!")
                 .await
                 .unwrap();
-            return Ok(());
         }
+        return Ok(());
     }
     let possible_building = string_to_building(&building.to_lowercase()).await;
     match possible_building.is_err() {
@@ -65,10 +82,25 @@ pub(crate) async fn build(
         return Ok(());
     }
     let tile = db::tiles::get_tile(x, y).await.unwrap();
+    let terrain = tile.terrain();
+    if !terrain.allows(&possible_building.clone().unwrap()) {
+        ctx.say(format!(
+            "{}s can't be built on {}!",
+            possible_building.unwrap().data().name,
+            terrain.data().name.to_lowercase()
+        ))
+        .await?;
+        return Ok(());
+    }
     let existing_buildings = tile.buildings;
     let mut used_space = 0;
-    for (building, amount) in existing_buildings {
-        used_space += building.data().space * amount as i32;
+    for (building, levels) in existing_buildings {
+        used_space += building.data().space * levels.total() as i32;
+    }
+    // Buildings still under construction have already claimed their space
+    let queued = db::construction::get_all_by_tile(x, y).await?;
+    for job in &queued {
+        used_space += job.building.data().space * job.amount as i32;
     }
     let building = possible_building.unwrap();
     let building_space = building.data().space;
@@ -84,50 +116,186 @@ pub(crate) async fn build(
         }
         return Ok(());
     }
-    let faction_tag = db::users::get_user(ctx.author().id.to_string())
+    let mut faction = db::factions::get_faction(user.faction.clone())
         .await
-        .unwrap()
-        .faction;
-    let mut faction = db::factions::get_faction(faction_tag).await.unwrap();
+        .unwrap();
     let details = building.data();
-    if details.cost > faction.production.money.floor() as i32 {
+    // Every building in the order is paid for, not just the first
+    let (cost, wood, metal) = building.cost_of(amount);
+    if cost > faction.production.money.floor() as i32 {
         ctx.say("You don't have enough money to build that!")
             .await
             .unwrap();
         return Ok(());
     }
-    if details.wood > faction.production.wood.floor() as i32 {
+    if let Some(refusal) = user.refuse_spending(cost as f32, faction.production.money) {
+        ctx.say(refusal).await.unwrap();
+        return Ok(());
+    }
+    if wood > faction.production.wood.floor() as i32 {
         ctx.say("You don't have enough wood to build that!")
             .await
             .unwrap();
         return Ok(());
     }
-    if details.metal > faction.production.metal.floor() as i32 {
+    if metal > faction.production.metal.floor() as i32 {
         ctx.say("You don't have enough metal to build that!")
             .await
             .unwrap();
         return Ok(());
     }
 
-    faction.production.money -= details.cost as f32;
-    faction.production.wood -= details.wood as f32;
-    faction.production.metal -= details.metal as f32;
+    faction.production.money -= cost as f32;
+    faction.production.wood -= wood as f32;
+    faction.production.metal -= metal as f32;
     db::factions::save_faction(faction).await.unwrap();
-    let mut tile = db::tiles::get_tile(x, y).await.unwrap();
-    let mut buildings = tile.buildings;
-    match buildings.get(&building) {
-        Some(existing_buildings) => {
-            buildings.insert(building, existing_buildings + amount as u32);
-        }
-        None => {
-            buildings.insert(building, amount as u32);
+
+    // Each tile has one crew, so work only starts once everything queued before it is done
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let started_at = queued.iter().map(|job| job.ready_at).fold(now, u64::max);
+    let ready_at = started_at + details.build_time * amount as u64;
+    let job = ConstructionJob {
+        id: format!("{:08x}", rand::rngs::OsRng.gen::<u32>()),
+        faction: user.faction,
+        x,
+        y,
+        building,
+        amount: amount as u32,
+        started_at,
+        ready_at,
+        ordered_by: user.uuid,
+        cost,
+        wood,
+        metal,
+    };
+    db::construction::add_job(job).await?;
+    ctx.say(format!(
+        "Construction of {} {}s has begun! They will be finished <t:{}:R>.",
+        amount, details.name, ready_at
+    ))
+    .await
+    .unwrap();
+
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "List the buildings your faction has under construction")
+)]
+pub(crate) async fn queue(ctx: Context<'_>) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(());
+    }
+    let jobs = db::construction::get_all_by_faction(user.faction.clone()).await?;
+    let mut list = "".to_string();
+    for job in &jobs {
+        list.push_str(&format!(
+            "`{}` {} {} at {}, {} - finished <t:{}:R>\n",
+            job.id,
+            job.amount,
+            job.building.data().name,
+            job.x,
+            job.y,
+            job.ready_at
+        ));
+    }
+    if list.is_empty() {
+        list = "Nothing is under construction".to_string();
+    }
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Construction queue for {}", user.faction))
+                .description(list)
+                .footer(|f| f.text("Use /build cancel <id> to cancel a job"))
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Cancel a construction job and get its resources back")
+)]
+pub(crate) async fn cancel(
+    ctx: Context<'_>,
+    #[description = "The ID of the job, from /build queue"] job: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(());
+    }
+    if !user.permitted(Permissions::Build) {
+        ctx.say("You don't have permission to cancel construction!")
+            .await?;
+        return Ok(());
+    }
+    let cancelled = match db::construction::get_job(job.trim().to_lowercase()).await? {
+        Some(found) if found.faction == user.faction => found,
+        _ => {
+            ctx.say("There's no job with that ID! Check `/build queue`.")
+                .await?;
+            return Ok(());
         }
+    };
+    // The job may have finished since we looked it up, in which case there's nothing to refund
+    if !db::construction::delete_job(cancelled.id.clone()).await? {
+        ctx.say("That job has already finished!").await?;
+        return Ok(());
     }
-    tile.buildings = buildings;
-    db::tiles::set_tile(tile).await.unwrap();
-    ctx.say(format!("You built {} {}s!", amount, details.name))
-        .await
-        .unwrap();
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let share = cancelled.refund_share(now);
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    faction.production.money += cancelled.cost as f32 * share;
+    faction.production.wood += cancelled.wood as f32 * share;
+    faction.production.metal += cancelled.metal as f32 * share;
+    db::factions::save_faction(faction).await?;
 
+    // Everything queued behind it on the same tile can start sooner
+    let remaining = db::construction::get_all_by_tile(cancelled.x, cancelled.y).await?;
+    for moved in db::construction::reschedule(remaining, now) {
+        db::construction::save_job(moved).await?;
+    }
+    let refunded = if share >= 1.0 {
+        "Your resources have been refunded.".to_string()
+    } else {
+        format!(
+            "Work had already started, so only {:.0}% of your resources have been refunded.",
+            share * 100.0
+        )
+    };
+    ctx.say(format!(
+        "Cancelled construction of {} {} at {}, {}. {}",
+        cancelled.amount,
+        cancelled.building.data().name,
+        cancelled.x,
+        cancelled.y,
+        refunded
+    ))
+    .await?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+use crate::misc::log_command_used;
+use crate::types::permissions::Permissions;
+use crate::{db, Context, Error};
+
+// How much the first claimed tile costs, and how much more each tile the faction already owns adds
+const CLAIM_BASE_COST: f32 = 100.0;
+const CLAIM_COST_PER_TILE: f32 = 25.0;
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Claim a tile next to your territory")
+)]
+pub(crate) async fn claim(
+    ctx: Context<'_>,
+    #[description = "The x coordinate of the tile to claim"] x: i32,
+    #[description = "The y coordinate of the tile to claim"] y: i32,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(());
+    }
+    if !user.permitted(Permissions::Build) {
+        ctx.say("You don't have permission to claim tiles!").await?;
+        return Ok(());
+    }
+    let mut tile = db::tiles::get_tile(x, y).await?;
+    if tile.faction == user.faction && tile.claimed {
+        ctx.say("You have already claimed that tile!").await?;
+        return Ok(());
+    }
+    if !tile.faction.is_empty() && tile.faction != user.faction {
+        ctx.say(format!(
+            "That tile belongs to {}! You'll have to take it by force.",
+            tile.faction
+        ))
+        .await?;
+        return Ok(());
+    }
+    let terrain = tile.terrain().data();
+    if !terrain.passable {
+        ctx.say(format!("You can't claim {}!", terrain.name.to_lowercase()))
+            .await?;
+        return Ok(());
+    }
+    // Territory has to grow outwards from what you already hold
+    let territory = db::tiles::get_all_by_faction(user.faction.clone()).await?;
+    if tile.faction != user.faction && !territory.iter().any(|owned| owned.is_adjacent(x, y)) {
+        ctx.say("You can only claim tiles next to your own territory!")
+            .await?;
+        return Ok(());
+    }
+
+    // Every tile you hold makes the next one more expensive
+    let cost = CLAIM_BASE_COST + CLAIM_COST_PER_TILE * territory.len() as f32;
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    if cost > faction.production.money {
+        ctx.say(format!(
+            "You don't have enough money to claim that tile! You need ${:.2}.",
+            cost
+        ))
+        .await?;
+        return Ok(());
+    }
+    if let Some(refusal) = user.refuse_spending(cost, faction.production.money) {
+        ctx.say(refusal).await?;
+        return Ok(());
+    }
+    faction.production.money -= cost;
+    db::factions::save_faction(faction).await?;
+
+    tile.faction = user.faction;
+    tile.occupied = true;
+    tile.claimed = true;
+    db::tiles::set_tile(tile).await?;
+    ctx.say(format!(
+        "You claimed {}, {} for ${:.2}! It will stay yours even when there's nobody on it.",
+        x, y, cost
+    ))
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use crate::conversions::string_to_building;
+use crate::misc::log_command_used;
+use crate::types::permissions::Permissions;
+use crate::{db, Context, Error};
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Tear down buildings to free up space")
+)]
+pub(crate) async fn demolish(
+    ctx: Context<'_>,
+    #[description_localized("en-US", "The building to demolish")] building: String,
+    #[description_localized("en-US", "The amount of buildings to demolish")] amount: u32,
+    #[description_localized("en-US", "The x coordinate of the tile to demolish on")] x: i32,
+    #[description_localized("en-US", "The y coordinate of the tile to demolish on")] y: i32,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if !user.permitted(Permissions::Build) {
+        ctx.say("You don't have permission to demolish buildings!")
+            .await?;
+        return Ok(());
+    }
+    if amount == 0 {
+        ctx.say("You can't demolish nothing!").await?;
+        return Ok(());
+    }
+    let building = match string_to_building(&building.to_lowercase()).await {
+        Ok(building) => building,
+        Err(_) => {
+            ctx.say("That's not a valid building!").await?;
+            return Ok(());
+        }
+    };
+    let (money, wood, metal) = match building.salvage(amount) {
+        Some(salvaged) => salvaged,
+        None => {
+            ctx.say("You can't demolish your capital!").await?;
+            return Ok(());
+        }
+    };
+    let mut tile = db::tiles::get_tile(x, y).await?;
+    if user.faction.is_empty() || tile.faction != user.faction {
+        ctx.say("You can't demolish buildings on tiles that aren't yours!")
+            .await?;
+        return Ok(());
+    }
+    let details = building.data();
+    let existing = tile.building_count(&building);
+    if existing < amount {
+        ctx.say(format!(
+            "There are only {} {}s on that tile!",
+            existing, details.name
+        ))
+        .await?;
+        return Ok(());
+    }
+    // The lowest level buildings are torn down first
+    tile.remove_buildings(&building, amount);
+    db::tiles::set_tile(tile).await?;
+
+    let mut faction = db::factions::get_faction(user.faction).await?;
+    faction.production.money += money;
+    faction.production.wood += wood;
+    faction.production.metal += metal;
+    // Don't wait for the background loop, the building is gone now
+    db::build_faction_production(faction).await?;
+
+    ctx.say(format!(
+        "You demolished {} {}s and salvaged ${:.2}, {} wood and {} metal.",
+        amount,
+        details.name,
+        money,
+        wood.floor(),
+        metal.floor()
+    ))
+    .await?;
+    Ok(())
+}
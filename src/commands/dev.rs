@@ -75,9 +75,7 @@ pub(crate) async fn clean_db(ctx: Context<'_>) -> Result<(), Error> {
     if !reply_admin(ctx).await? {
         return Ok(());
     }
-    db::cleaners::clean_factions().await?;
-    db::cleaners::clean_tiles().await?;
-    db::cleaners::clean_users().await?;
+    db::cleaners::clean_all().await?;
     ctx.say("Database is nice and squeaky clean!").await?;
     Ok(())
 }
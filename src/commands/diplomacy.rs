@@ -0,0 +1,330 @@
+use std::time::SystemTime;
+
+use crate::misc::log_command_used;
+use crate::types::diplomacy::{Relation, Stance};
+use crate::types::permissions::Permissions;
+use crate::{db, Context, Error};
+
+#[poise::command(
+    slash_command,
+    subcommands("war", "peace", "pact", "alliance", "accept", "decline", "status")
+)]
+pub(crate) async fn diplomacy(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Checks that the user is allowed to conduct diplomacy with the target faction and gets the
+/// relation between the two. Replies to the user and returns None if they can't
+async fn get_relation_for(
+    ctx: Context<'_>,
+    target: &str,
+) -> Result<Option<(String, Relation)>, Error> {
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(None);
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(None);
+    }
+    if !user.permitted(Permissions::War) {
+        ctx.say("You don't have permission to conduct diplomacy!")
+            .await?;
+        return Ok(None);
+    }
+    let target = target.to_uppercase();
+    if target == user.faction {
+        ctx.say("You can't conduct diplomacy with your own faction!")
+            .await?;
+        return Ok(None);
+    }
+    if !db::factions::faction_exists(target.clone()).await? {
+        ctx.say("That faction doesn't exist!").await?;
+        return Ok(None);
+    }
+    let relation = db::relations::get_relation(user.faction.clone(), target).await?;
+    Ok(Some((user.faction, relation)))
+}
+
+/// Offers a new stance to another faction. The stance only comes into effect once they accept
+async fn propose(ctx: Context<'_>, target: String, stance: Stance) -> Result<(), Error> {
+    let (tag, mut relation) = match get_relation_for(ctx, &target).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let other = relation.other(&tag);
+    if relation.stance == stance {
+        ctx.say(format!(
+            "You already have {} with {}!",
+            stance.description(),
+            other
+        ))
+        .await?;
+        return Ok(());
+    }
+    if stance == Stance::Alliance && relation.stance == Stance::War {
+        ctx.say("You need to make peace before you can form an alliance!")
+            .await?;
+        return Ok(());
+    }
+    if stance == Stance::NonAggression && relation.stance == Stance::War {
+        ctx.say("You need to make peace before you can agree to a non-aggression pact!")
+            .await?;
+        return Ok(());
+    }
+    if stance == Stance::NonAggression && relation.stance == Stance::Alliance {
+        ctx.say("You are already allied, which covers everything a non-aggression pact would!")
+            .await?;
+        return Ok(());
+    }
+    // If they already offered the same thing, just take them up on it
+    if relation.proposal == Some(stance.clone()) && relation.proposed_by == other {
+        return accept_proposal(ctx, tag, relation).await;
+    }
+    relation.proposal = Some(stance.clone());
+    relation.proposed_by = tag;
+    db::relations::save_relation(relation).await?;
+    ctx.say(format!(
+        "You have offered {} to {}. They can respond with `/diplomacy accept` or `/diplomacy decline`.",
+        stance.description(),
+        other
+    ))
+    .await?;
+    Ok(())
+}
+
+async fn accept_proposal(
+    ctx: Context<'_>,
+    tag: String,
+    mut relation: Relation,
+) -> Result<(), Error> {
+    let stance = relation.proposal.clone().unwrap();
+    let other = relation.other(&tag);
+    relation.stance = stance.clone();
+    relation.since = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    relation.proposal = None;
+    relation.proposed_by = "".to_string();
+    db::relations::save_relation(relation).await?;
+    ctx.say(format!(
+        "You now have {} with {}!",
+        stance.description(),
+        other
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Declare war on another faction")
+)]
+pub(crate) async fn war(
+    ctx: Context<'_>,
+    #[description = "The tag of the faction to declare war on"] faction: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let (tag, mut relation) = match get_relation_for(ctx, &faction).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let other = relation.other(&tag);
+    if relation.stance == Stance::War {
+        ctx.say(format!("You are already at war with {}!", other))
+            .await?;
+        return Ok(());
+    }
+    let broken = relation.stance.clone();
+    relation.stance = Stance::War;
+    relation.since = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    relation.proposal = None;
+    relation.proposed_by = "".to_string();
+    db::relations::save_relation(relation).await?;
+    let mut message = format!(
+        "*The drums of war beat...*\n\nYou have declared war on {}!",
+        other
+    );
+    if broken != Stance::Neutral {
+        message.push_str(&format!(
+            " In doing so, you broke {}.",
+            broken.description()
+        ));
+    }
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Offer a peace treaty to another faction")
+)]
+pub(crate) async fn peace(
+    ctx: Context<'_>,
+    #[description = "The tag of the faction to offer peace to"] faction: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    propose(ctx, faction, Stance::Peace).await
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Offer a non-aggression pact to another faction")
+)]
+pub(crate) async fn pact(
+    ctx: Context<'_>,
+    #[description = "The tag of the faction to offer a pact to"] faction: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    propose(ctx, faction, Stance::NonAggression).await
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Offer an alliance to another faction")
+)]
+pub(crate) async fn alliance(
+    ctx: Context<'_>,
+    #[description = "The tag of the faction to offer an alliance to"] faction: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    propose(ctx, faction, Stance::Alliance).await
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Accept an offer from another faction")
+)]
+pub(crate) async fn accept(
+    ctx: Context<'_>,
+    #[description = "The tag of the faction whose offer you are accepting"] faction: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let (tag, relation) = match get_relation_for(ctx, &faction).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    if relation.proposal.is_none() || relation.proposed_by == tag {
+        ctx.say(format!(
+            "{} hasn't offered you anything!",
+            relation.other(&tag)
+        ))
+        .await?;
+        return Ok(());
+    }
+    accept_proposal(ctx, tag, relation).await
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Decline an offer from another faction, or withdraw your own")
+)]
+pub(crate) async fn decline(
+    ctx: Context<'_>,
+    #[description = "The tag of the other faction"] faction: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let (tag, mut relation) = match get_relation_for(ctx, &faction).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let other = relation.other(&tag);
+    let proposal = match relation.proposal.clone() {
+        Some(proposal) => proposal,
+        None => {
+            ctx.say(format!("There is nothing pending with {}!", other))
+                .await?;
+            return Ok(());
+        }
+    };
+    let withdrawn = relation.proposed_by == tag;
+    relation.proposal = None;
+    relation.proposed_by = "".to_string();
+    db::relations::save_relation(relation).await?;
+    if withdrawn {
+        ctx.say(format!(
+            "You withdrew your offer of {} to {}.",
+            proposal.description(),
+            other
+        ))
+        .await?;
+    } else {
+        ctx.say(format!(
+            "You declined {}'s offer of {}.",
+            other,
+            proposal.description()
+        ))
+        .await?;
+    }
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "List your faction's relations with other factions")
+)]
+pub(crate) async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(());
+    }
+    let relations = db::relations::get_all_by_faction(user.faction.clone()).await?;
+    let mut standing = "".to_string();
+    let mut pending = "".to_string();
+    for relation in relations {
+        let other = relation.other(&user.faction);
+        if relation.stance != Stance::Neutral {
+            standing.push_str(&format!("{}: {}\n", other, relation.stance.description()));
+        }
+        if let Some(proposal) = relation.proposal {
+            if relation.proposed_by == user.faction {
+                pending.push_str(&format!(
+                    "You offered {} to {}\n",
+                    proposal.description(),
+                    other
+                ));
+            } else {
+                pending.push_str(&format!(
+                    "{} offered you {}\n",
+                    other,
+                    proposal.description()
+                ));
+            }
+        }
+    }
+    if standing.is_empty() {
+        standing = "None".to_string();
+    }
+    if pending.is_empty() {
+        pending = "None".to_string();
+    }
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Diplomacy for {}", user.faction))
+                .field("Relations", standing, false)
+                .field("Pending offers", pending, false)
+        })
+    })
+    .await?;
+    Ok(())
+}
@@ -1,26 +1,44 @@
 use std::time::SystemTime;
 
+use poise::serenity_prelude as serenity;
 use poise::Modal;
 use rand::Rng;
 use regex::Regex;
 use tokio::time;
 
-use crate::conversions::modal_to_faction;
+use crate::config::get_config;
+use crate::conversions::{modal_to_faction, string_to_colour, string_to_permission};
 use crate::db::tiles::blank_tile;
-use crate::image::VIEW_DISTANCE;
+use crate::image::MAX_MAP_RADIUS;
 use crate::misc::log_command_used;
-use crate::types::buildings::Building;
+use crate::terrain::{get_terrain, TerrainGenerator};
+use crate::types::buildings::{Building, Levels};
+use crate::types::factions::{tag_colour, DEFAULT_TAX_RATE};
 use crate::types::permissions::Permissions;
 use crate::types::units::Unit;
+use crate::types::users::User;
 use crate::{db, Context, Data, Error};
 
-const CAPITAL_PLACE_RANGE: i32 = VIEW_DISTANCE * 3;
+const CAPITAL_PLACE_RANGE: i32 = MAX_MAP_RADIUS * 3;
 const INFO_INLINE: bool = true;
 const MAX_CAPITAL_DIST: i32 = 500;
 
 type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, Error>;
 
-#[poise::command(slash_command, subcommands("create", "info"))]
+#[poise::command(
+    slash_command,
+    subcommands(
+        "create",
+        "info",
+        "invite",
+        "accept",
+        "leave",
+        "kick",
+        "transfer",
+        "permissions",
+        "tax"
+    )
+)]
 pub(crate) async fn faction(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
@@ -44,6 +62,10 @@ pub(crate) struct FactionModal {
     #[name = "The location of the faction's capital"]
     #[placeholder = "0, 0 \n(Leave blank for a random location)"]
     pub(crate) faction_location: Option<String>,
+    #[name = "The colour of your territory on the map"]
+    #[placeholder = "#3A7BD5 \n(Leave blank for one based on your tag)"]
+    #[max_length = 7]
+    pub(crate) faction_colour: Option<String>,
 }
 
 #[poise::command(
@@ -67,6 +89,17 @@ pub(crate) async fn create(ctx: ApplicationContext<'_>) -> Result<(), Error> {
         return Ok(());
     }
     let data = FactionModal::execute(ctx).await?.unwrap();
+    let colour = match data.faction_colour.as_deref().map(str::trim) {
+        Some(colour) if !colour.is_empty() => match string_to_colour(colour) {
+            Ok(colour) => colour,
+            Err(_) => {
+                ctx.say("Invalid colour! Needs to be a hex colour like `#3A7BD5`")
+                    .await?;
+                return Ok(());
+            }
+        },
+        _ => tag_colour(&data.faction_tag),
+    };
     let pattern = Regex::new(r"(-?\d+)[,|\s]*(-?\d+)").unwrap();
     let location_chosen = data.faction_location.is_some();
     if location_chosen && !pattern.is_match(&data.faction_location.as_ref().unwrap()) {
@@ -101,6 +134,10 @@ pub(crate) async fn create(ctx: ApplicationContext<'_>) -> Result<(), Error> {
         let y_range = (y + CAPITAL_PLACE_RANGE, y - CAPITAL_PLACE_RANGE);
         let x_range = (x + CAPITAL_PLACE_RANGE, x - CAPITAL_PLACE_RANGE);
         let valid = !db::tiles::any_exist(x_range, y_range).await?;
+        if !get_terrain(x, y).data().passable {
+            ctx.say("You can't found your capital on water!").await?;
+            return Ok(());
+        }
         if !valid {
             ctx.say("That location is too close to an existing faction!")
                 .await?;
@@ -112,6 +149,8 @@ pub(crate) async fn create(ctx: ApplicationContext<'_>) -> Result<(), Error> {
         let mut distance = CAPITAL_PLACE_RANGE;
         // get a random position `distance` tiles from 0,0 and increment it each time there are any taken tiles in range
         let mut random_generator = rand::rngs::OsRng;
+        // Nobody wants to found their capital in the middle of a lake
+        let terrain = TerrainGenerator::new(get_config().perlin_seed);
         loop {
             let horizontal = random_generator.gen_range(0..2) == 0;
             let top_or_left = random_generator.gen_range(0..2) == 0;
@@ -120,7 +159,8 @@ pub(crate) async fn create(ctx: ApplicationContext<'_>) -> Result<(), Error> {
                 let y = random_generator.gen_range(-distance..distance);
                 let y_range = (y + CAPITAL_PLACE_RANGE, y - CAPITAL_PLACE_RANGE);
                 let x_range = (x + CAPITAL_PLACE_RANGE, x - CAPITAL_PLACE_RANGE);
-                let valid = !db::tiles::any_exist(x_range, y_range).await?;
+                let valid = !db::tiles::any_exist(x_range, y_range).await?
+                    && terrain.at(x, y).data().passable;
                 if valid {
                     faction_location = (x, y);
                     break;
@@ -132,7 +172,8 @@ pub(crate) async fn create(ctx: ApplicationContext<'_>) -> Result<(), Error> {
                 let y = if top_or_left { distance } else { -distance };
                 let y_range = (y + CAPITAL_PLACE_RANGE, y - CAPITAL_PLACE_RANGE);
                 let x_range = (x + CAPITAL_PLACE_RANGE, x - CAPITAL_PLACE_RANGE);
-                let valid = !db::tiles::any_exist(x_range, y_range).await?;
+                let valid = !db::tiles::any_exist(x_range, y_range).await?
+                    && terrain.at(x, y).data().passable;
                 if valid {
                     faction_location = (x, y);
                     break;
@@ -154,9 +195,12 @@ pub(crate) async fn create(ctx: ApplicationContext<'_>) -> Result<(), Error> {
     converted_data.production.metal = 100.0;
     converted_data.production.food = 500.0;
     converted_data.production.population = 100.0;
+    converted_data.production.population_cap = 100;
+    converted_data.production.tax_rate = DEFAULT_TAX_RATE;
     converted_data.production.happiness = 80.0;
     converted_data.capital_x = faction_location.0;
     converted_data.capital_y = faction_location.1;
+    converted_data.colour = Some(colour);
     converted_data.production.last_updated = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
@@ -168,8 +212,11 @@ pub(crate) async fn create(ctx: ApplicationContext<'_>) -> Result<(), Error> {
     let mut faction_tile = blank_tile(faction_location.0, faction_location.1).await;
     faction_tile.faction = tag.clone();
     faction_tile.occupied = true;
+    faction_tile.claimed = true;
     faction_tile.units.insert(Unit::Citizen, 100);
-    let insert_result = faction_tile.buildings.insert(Building::Capital, 1);
+    let insert_result = faction_tile
+        .buildings
+        .insert(Building::Capital, Levels::new(1, 1));
     if insert_result.is_some() {
         panic!(
             "Failed to insert building into tile! Capital already exists? Tried to set at {}, {}",
@@ -209,6 +256,7 @@ pub(crate) async fn info(ctx: Context<'_>) -> Result<(), Error> {
     let faction = db::factions::get_faction(user.faction.clone()).await?;
 
     let leader = db::users::get_user(faction.leader.clone()).await?;
+    let [r, g, b] = faction.colour();
 
     ctx.send(|e| {
         e.embed(|embed| {
@@ -216,20 +264,44 @@ pub(crate) async fn info(ctx: Context<'_>) -> Result<(), Error> {
                 .title(faction.name)
                 .description(faction.description)
                 .field("Tag", faction.tag, INFO_INLINE)
+                .field(
+                    "Colour",
+                    format!("#{:02X}{:02X}{:02X}", r, g, b),
+                    INFO_INLINE,
+                )
                 .field(
                     "Capital location",
                     format!("{}, {}", faction.capital_x, faction.capital_y),
                     INFO_INLINE,
                 )
                 .field("Leader", leader.username, INFO_INLINE)
+                .field("Members", format!("{}", faction.members.len()), INFO_INLINE)
                 .field(
                     "Population",
-                    format!("{}", faction.production.population.floor() as i32),
+                    format!(
+                        "{} / {}",
+                        faction.production.population.floor() as i32,
+                        faction.production.population_cap
+                    ),
+                    INFO_INLINE,
+                )
+                .field(
+                    "Happiness",
+                    format!("{:.0}%", faction.production.happiness),
+                    INFO_INLINE,
+                )
+                .field(
+                    "Tax rate",
+                    format!("{:.0}%", faction.production.tax_rate),
                     INFO_INLINE,
                 )
                 .field(
                     "Money",
-                    format!("${:.2}", faction.production.money),
+                    format!(
+                        "${:.2} (${:.2}/day)",
+                        faction.production.money,
+                        faction.production.money_per_second * 60.0 * 60.0 * 24.0
+                    ),
                     INFO_INLINE,
                 )
                 .field(
@@ -252,4 +324,480 @@ pub(crate) async fn info(ctx: Context<'_>) -> Result<(), Error> {
     .await?;
 
     return Ok(());
+}
+
+/// Gets the user calling the command, as long as they are registered and in a faction. Replies to
+/// the user and returns None otherwise
+async fn get_member(ctx: Context<'_>) -> Result<Option<User>, Error> {
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(None);
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(None);
+    }
+    Ok(Some(user))
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Invite someone to join your faction")
+)]
+pub(crate) async fn invite(
+    ctx: Context<'_>,
+    #[description = "The person to invite"] member: serenity::User,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let user = match get_member(ctx).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    if !user.permitted(Permissions::Leader) {
+        ctx.say("Only faction leaders can invite people!").await?;
+        return Ok(());
+    }
+    let uuid = member.id.to_string();
+    if !db::users::user_exists(uuid.clone()).await? {
+        ctx.say(format!(
+            "{} needs to register with `/register` before they can be invited!",
+            member.name
+        ))
+        .await?;
+        return Ok(());
+    }
+    if !db::users::get_user(uuid.clone()).await?.faction.is_empty() {
+        ctx.say(format!("{} is already in a faction!", member.name))
+            .await?;
+        return Ok(());
+    }
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    if faction.invites.contains(&uuid) {
+        ctx.say(format!("{} has already been invited!", member.name))
+            .await?;
+        return Ok(());
+    }
+    faction.invites.push(uuid);
+    let tag = faction.tag.clone();
+    let name = faction.name.clone();
+    db::factions::save_faction(faction).await?;
+    ctx.say(format!(
+        "<@{}>, you have been invited to join {}! Use `/faction accept {}` to join.",
+        member.id, name, tag
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Accept an invite to join a faction")
+)]
+pub(crate) async fn accept(
+    ctx: Context<'_>,
+    #[description = "The tag of the faction you were invited to"] tag: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let mut user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if !user.faction.is_empty() {
+        ctx.say("You are already in a faction!").await?;
+        return Ok(());
+    }
+    let tag = tag.to_uppercase();
+    if !db::factions::faction_exists(tag.clone()).await? {
+        ctx.say("That faction doesn't exist!").await?;
+        return Ok(());
+    }
+    let mut faction = db::factions::get_faction(tag.clone()).await?;
+    if !faction.invites.contains(&user.uuid) {
+        ctx.say("You haven't been invited to that faction!").await?;
+        return Ok(());
+    }
+    faction.invites.retain(|uuid| uuid != &user.uuid);
+    faction.members.push(user.uuid.clone());
+    let name = faction.name.clone();
+    db::factions::save_faction(faction).await?;
+    user.faction = tag;
+    user.permissions.clear();
+    db::users::save_user(user).await?;
+    ctx.say(format!("Welcome to {}!", name)).await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Leave your faction")
+)]
+pub(crate) async fn leave(ctx: Context<'_>) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let mut user = match get_member(ctx).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    faction.members.retain(|uuid| uuid != &user.uuid);
+    if faction.members.is_empty() {
+        // Nobody is left to run the place, so the faction falls with them
+        let name = faction.name.clone();
+        db::factions::disband_faction(faction.tag).await?;
+        ctx.say(format!(
+            "*The last banner is lowered...*\n\nYou were the last member of {}, so it has been disbanded and its lands abandoned.",
+            name
+        ))
+        .await?;
+        return Ok(());
+    }
+    let mut message = format!("You have left {}.", faction.name);
+    if faction.leader == user.uuid {
+        // Leadership passes to whoever has been around the longest
+        let successor_id = faction.members[0].clone();
+        let mut successor = db::users::get_user(successor_id.clone()).await?;
+        if !successor.permissions.contains(&Permissions::Leader) {
+            successor.permissions.push(Permissions::Leader);
+        }
+        message.push_str(&format!(
+            " Leadership has passed to {}.",
+            successor.username
+        ));
+        db::users::save_user(successor).await?;
+        faction.leader = successor_id;
+    }
+    db::factions::save_faction(faction).await?;
+    user.faction = "".to_string();
+    user.permissions.clear();
+    db::users::save_user(user).await?;
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Remove someone from your faction")
+)]
+pub(crate) async fn kick(
+    ctx: Context<'_>,
+    #[description = "The member to remove"] member: serenity::User,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let user = match get_member(ctx).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    if !user.permitted(Permissions::Leader) {
+        ctx.say("Only faction leaders can kick members!").await?;
+        return Ok(());
+    }
+    let uuid = member.id.to_string();
+    if uuid == user.uuid {
+        ctx.say("You can't kick yourself! Use `/faction leave` instead.")
+            .await?;
+        return Ok(());
+    }
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    if !faction.members.contains(&uuid) {
+        ctx.say(format!("{} isn't in your faction!", member.name))
+            .await?;
+        return Ok(());
+    }
+    if faction.leader == uuid {
+        ctx.say("You can't kick the leader of the faction!").await?;
+        return Ok(());
+    }
+    faction.members.retain(|existing| existing != &uuid);
+    db::factions::save_faction(faction).await?;
+    let mut kicked = db::users::get_user(uuid).await?;
+    kicked.faction = "".to_string();
+    kicked.permissions.clear();
+    db::users::save_user(kicked).await?;
+    ctx.say(format!(
+        "{} has been removed from the faction.",
+        member.name
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Hand leadership of your faction to another member")
+)]
+pub(crate) async fn transfer(
+    ctx: Context<'_>,
+    #[description = "The member who will become the new leader"] member: serenity::User,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let mut user = match get_member(ctx).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    if faction.leader != user.uuid {
+        ctx.say("Only the leader of the faction can hand over leadership!")
+            .await?;
+        return Ok(());
+    }
+    let uuid = member.id.to_string();
+    if uuid == user.uuid {
+        ctx.say("You are already the leader!").await?;
+        return Ok(());
+    }
+    if !faction.members.contains(&uuid) {
+        ctx.say(format!("{} isn't in your faction!", member.name))
+            .await?;
+        return Ok(());
+    }
+    let mut successor = db::users::get_user(uuid.clone()).await?;
+    if !successor.permissions.contains(&Permissions::Leader) {
+        successor.permissions.push(Permissions::Leader);
+    }
+    db::users::save_user(successor).await?;
+    user.permissions
+        .retain(|permission| permission != &Permissions::Leader);
+    db::users::save_user(user).await?;
+    faction.leader = uuid;
+    db::factions::save_faction(faction).await?;
+    ctx.say(format!("{} is now the leader of the faction!", member.name))
+        .await?;
+    Ok(())
+}
+
+async fn autocomplete_permission<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    Permissions::all()
+        .into_iter()
+        .map(|permission| permission.to_string())
+        .filter(move |name| name.to_lowercase().starts_with(&partial.to_lowercase()))
+}
+
+/// Checks that the user calling the command can manage the permissions of the given member, and
+/// gets both of them. Replies to the user and returns None if they can't
+async fn get_managed_member(
+    ctx: Context<'_>,
+    member: &serenity::User,
+) -> Result<Option<(User, User)>, Error> {
+    let user = match get_member(ctx).await? {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    if !user.permitted(Permissions::Leader) {
+        ctx.say("Only faction leaders can manage permissions!")
+            .await?;
+        return Ok(None);
+    }
+    let uuid = member.id.to_string();
+    if !db::users::user_exists(uuid.clone()).await? {
+        ctx.say(format!("{} isn't in your faction!", member.name))
+            .await?;
+        return Ok(None);
+    }
+    let target = db::users::get_user(uuid).await?;
+    if target.faction != user.faction {
+        ctx.say(format!("{} isn't in your faction!", member.name))
+            .await?;
+        return Ok(None);
+    }
+    Ok(Some((user, target)))
+}
+
+#[poise::command(slash_command, subcommands("grant", "revoke", "list"))]
+pub(crate) async fn permissions(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Give a member of your faction a permission")
+)]
+pub(crate) async fn grant(
+    ctx: Context<'_>,
+    #[description = "The member to give the permission to"] member: serenity::User,
+    #[description = "The permission to give"]
+    #[autocomplete = "autocomplete_permission"]
+    permission: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let (_, mut target) = match get_managed_member(ctx, &member).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let permission = match string_to_permission(&permission).await {
+        Ok(permission) => permission,
+        Err(_) => {
+            ctx.say("That's not a valid permission!").await?;
+            return Ok(());
+        }
+    };
+    if target.permissions.contains(&permission) {
+        ctx.say(format!("{} already has {}!", member.name, permission))
+            .await?;
+        return Ok(());
+    }
+    target.permissions.push(permission.clone());
+    db::users::save_user(target).await?;
+    ctx.say(format!("Gave {} to {}.", permission, member.name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Take a permission away from a member of your faction")
+)]
+pub(crate) async fn revoke(
+    ctx: Context<'_>,
+    #[description = "The member to take the permission from"] member: serenity::User,
+    #[description = "The permission to take away"]
+    #[autocomplete = "autocomplete_permission"]
+    permission: String,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let (user, mut target) = match get_managed_member(ctx, &member).await? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let permission = match string_to_permission(&permission).await {
+        Ok(permission) => permission,
+        Err(_) => {
+            ctx.say("That's not a valid permission!").await?;
+            return Ok(());
+        }
+    };
+    if !target.permissions.contains(&permission) {
+        ctx.say(format!("{} doesn't have {}!", member.name, permission))
+            .await?;
+        return Ok(());
+    }
+    if permission == Permissions::Leader {
+        let faction = db::factions::get_faction(user.faction.clone()).await?;
+        if faction.leader == target.uuid {
+            ctx.say("You can't take Leader away from the head of the faction! Use `/faction transfer` instead.")
+                .await?;
+            return Ok(());
+        }
+        let mut leaders = 0;
+        for uuid in faction.members {
+            if db::users::get_user(uuid)
+                .await?
+                .permissions
+                .contains(&Permissions::Leader)
+            {
+                leaders += 1;
+            }
+        }
+        if leaders <= 1 {
+            ctx.say("You can't remove the last leader of the faction!")
+                .await?;
+            return Ok(());
+        }
+    }
+    target
+        .permissions
+        .retain(|existing| existing != &permission);
+    db::users::save_user(target).await?;
+    ctx.say(format!("Took {} away from {}.", permission, member.name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "List the permissions a member of your faction has")
+)]
+pub(crate) async fn list(
+    ctx: Context<'_>,
+    #[description = "The member to check. Leave blank for yourself"] member: Option<serenity::User>,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let user = match get_member(ctx).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    let target = match member {
+        Some(member) => {
+            let uuid = member.id.to_string();
+            if !db::users::user_exists(uuid.clone()).await? {
+                ctx.say(format!("{} isn't in your faction!", member.name))
+                    .await?;
+                return Ok(());
+            }
+            let target = db::users::get_user(uuid).await?;
+            if target.faction != user.faction {
+                ctx.say(format!("{} isn't in your faction!", member.name))
+                    .await?;
+                return Ok(());
+            }
+            target
+        }
+        None => user,
+    };
+    let mut granted = "".to_string();
+    for permission in Permissions::all() {
+        if target.permitted(permission.clone()) {
+            granted.push_str(&format!("{}\n", permission));
+        }
+    }
+    if granted.is_empty() {
+        granted = "None".to_string();
+    }
+    ctx.send(|b| {
+        b.embed(|e| {
+            e.title(format!("Permissions for {}", target.username))
+                .description(granted)
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Set how much your faction taxes its people")
+)]
+pub(crate) async fn tax(
+    ctx: Context<'_>,
+    #[description = "The tax rate, from 0 to 100 percent"]
+    #[min = 0]
+    #[max = 100]
+    percent: u32,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    let user = match get_member(ctx).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+    if !user.permitted(Permissions::Leader) {
+        ctx.say("Only faction leaders can set taxes!").await?;
+        return Ok(());
+    }
+    let mut faction = db::factions::get_faction(user.faction).await?;
+    let old_rate = faction.production.tax_rate;
+    faction.production.tax_rate = percent as f64;
+    db::factions::save_faction(faction).await?;
+    let mut message = format!("Taxes set to {}%.", percent);
+    if percent as f64 > old_rate {
+        message
+            .push_str(" Your coffers will fill faster, but your people won't be happy about it.");
+    } else if (percent as f64) < old_rate {
+        message.push_str(" Your people will be happier, but your income will drop.");
+    }
+    ctx.say(message).await?;
+    Ok(())
 }
\ No newline at end of file
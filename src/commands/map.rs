@@ -1,17 +1,32 @@
+use std::collections::HashMap;
 use std::io::Cursor;
+use std::time::SystemTime;
 
 use poise::serenity_prelude::AttachmentType;
 
 use db::tiles;
 
 use crate::conversions::{bytes_to_string, split_tiles};
+use crate::db::movements::HISTORY_HOURS;
+use crate::db::snapshots::{replay, SNAPSHOT_HOURS};
 use crate::db::tiles::{blank_tile, invert_y};
+use crate::image::{
+    overview_range, pick_frames, DEFAULT_MAP_RADIUS, DEFAULT_OVERVIEW_ZOOM, DEFAULT_TILE_PX,
+    MAX_MAP_RADIUS, MAX_TIMELAPSE_FRAMES, TIMELAPSE_TILE_PX,
+};
 use crate::misc::log_command_used;
-use crate::types::map::Tile;
+use crate::render_cache;
+use crate::render_cache::MapKey;
+use crate::types::factions::tag_colour;
+use crate::types::map::{Snapshot, Tile};
+use crate::vision::SCOUT_SIGHT_RANGE;
 use crate::{db, Context, Error};
 
 // The parent command. Doesn't really need to do anything.
-#[poise::command(slash_command, subcommands("position", "dev", "capital"))]
+#[poise::command(
+    slash_command,
+    subcommands("position", "dev", "capital", "overview", "timelapse")
+)]
 pub(crate) async fn map(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
@@ -28,6 +43,18 @@ pub(crate) async fn position(
     ctx: Context<'_>,
     #[description = "X coordinate of the centre tile"] x: i32,
     #[description = "Y coordinate of the centre tile"] y: i32,
+    #[description = "Show troop movements from the last this many hours"]
+    #[min = 1]
+    #[max = 72]
+    history: Option<u64>,
+    #[description = "How many tiles out from the centre to show"]
+    #[min = 1]
+    #[max = 10]
+    radius: Option<i32>,
+    #[description = "Shrink the map so each tile is this many pixels across instead of 150"]
+    #[min = 40]
+    #[max = 150]
+    tile_px: Option<u32>,
 ) -> Result<(), Error> {
     log_command_used(ctx).await;
     if !db::users::user_exists(ctx.author().id.to_string()).await? {
@@ -39,7 +66,15 @@ pub(crate) async fn position(
     let tag = db::users::get_user(ctx.author().id.to_string())
         .await?
         .faction;
-    let (data, _) = create_reply(x, y, tag).await?;
+    let (data, _) = create_reply(
+        x,
+        y,
+        tag,
+        history,
+        radius.unwrap_or(DEFAULT_MAP_RADIUS),
+        tile_px.unwrap_or(DEFAULT_TILE_PX),
+    )
+    .await?;
 
     let attachment = AttachmentType::Bytes {
         data: std::borrow::Cow::Owned(data.into_inner()),
@@ -72,7 +107,8 @@ pub(crate) async fn dev(
     let tag = db::users::get_user(ctx.author().id.to_string())
         .await?
         .faction;
-    let (data, dev_message) = create_reply(x, y, tag).await?;
+    let (data, dev_message) =
+        create_reply(x, y, tag, None, DEFAULT_MAP_RADIUS, DEFAULT_TILE_PX).await?;
     let mut send_message = dev_message;
     // Since the file was created in the create_reply function, get the size here
     // We could get it in the function, but that means we have to open the file twice and it's
@@ -97,7 +133,17 @@ track_edits,
 // ephemeral,
 description_localized("en-US", "Automatically gets the map of your faction's capital")
 )]
-pub(crate) async fn capital(ctx: Context<'_>) -> Result<(), Error> {
+pub(crate) async fn capital(
+    ctx: Context<'_>,
+    #[description = "How many tiles out from your capital to show"]
+    #[min = 1]
+    #[max = 10]
+    radius: Option<i32>,
+    #[description = "Shrink the map so each tile is this many pixels across instead of 150"]
+    #[min = 40]
+    #[max = 150]
+    tile_px: Option<u32>,
+) -> Result<(), Error> {
     log_command_used(ctx).await;
     if !db::users::user_exists(ctx.author().id.to_string()).await? {
         ctx.say("You need to register first!\nUse `/register` to join!")
@@ -118,7 +164,15 @@ pub(crate) async fn capital(ctx: Context<'_>) -> Result<(), Error> {
         .faction;
     let faction = db::factions::get_faction(faction_tag.clone()).await?;
     let (x, y) = (faction.capital_x, faction.capital_y);
-    let (data, _) = create_reply(x, y, faction_tag).await?;
+    let (data, _) = create_reply(
+        x,
+        y,
+        faction_tag,
+        None,
+        radius.unwrap_or(DEFAULT_MAP_RADIUS),
+        tile_px.unwrap_or(DEFAULT_TILE_PX),
+    )
+    .await?;
     let attachment = AttachmentType::Bytes {
         data: std::borrow::Cow::Owned(data.into_inner()),
         filename: "map.png".to_string(),
@@ -128,92 +182,321 @@ pub(crate) async fn capital(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-// This is the function that actually does all the work. Creates the image and the status message
+// Zoomed out map of a large area that only shows who owns what, for planning where to expand
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Get a zoomed out map of who owns what")
+)]
+pub(crate) async fn overview(
+    ctx: Context<'_>,
+    #[description = "How many pixels each tile takes up. Lower shows more of the world"]
+    #[min = 1]
+    #[max = 10]
+    zoom: Option<u32>,
+    #[description = "X coordinate of the centre tile. Defaults to your capital"] x: Option<i32>,
+    #[description = "Y coordinate of the centre tile. Defaults to your capital"] y: Option<i32>,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+    let tag = db::users::get_user(ctx.author().id.to_string())
+        .await?
+        .faction;
+    let (capital_x, capital_y) = if tag.is_empty() {
+        (0, 0)
+    } else {
+        let faction = db::factions::get_faction(tag.clone()).await?;
+        (faction.capital_x, faction.capital_y)
+    };
+    let centre = (x.unwrap_or(capital_x), y.unwrap_or(capital_y));
+    let zoom = zoom.unwrap_or(DEFAULT_OVERVIEW_ZOOM).clamp(1, 10);
+    let (x_range, y_range) = overview_range(centre, zoom);
+
+    // One query for the whole area, since most of it won't have been saved anyway
+    let mut visibility = db::tiles::get_visibility(tag.clone()).await?;
+    let mut tiles: HashMap<(i32, i32), Tile> = HashMap::new();
+    for tile in tiles::get_many(x_range, y_range).await? {
+        if tile.faction == tag || visibility.can_see(tile.x, tile.y) {
+            tiles.insert((tile.x, tile.y), visibility.mask(tile, &tag));
+        }
+    }
+    for sighting in db::intel::get_many(tag.clone(), x_range, y_range).await? {
+        if visibility.can_see(sighting.x, sighting.y)
+            || tiles.contains_key(&(sighting.x, sighting.y))
+        {
+            continue;
+        }
+        visibility
+            .remembered
+            .insert((sighting.x, sighting.y), sighting.seen_at);
+        tiles.insert((sighting.x, sighting.y), sighting.tile);
+    }
+    let tiles: Vec<Tile> = tiles.into_values().collect();
+    let colours = faction_colours(&tiles).await?;
+    let image = crate::image::draw_overview(tiles, centre, zoom, tag, visibility, colours).await;
+    let attachment = AttachmentType::Bytes {
+        data: std::borrow::Cow::Owned(crate::image::encode_png(image).await),
+        filename: "overview.png".to_string(),
+    };
+    ctx.send(|b| {
+        b.attachment(attachment).content(format!(
+            "Showing {} to {}, {} to {} at {}px per tile",
+            x_range.0, x_range.1, y_range.0, y_range.1, zoom
+        ))
+    })
+    .await?;
+    Ok(())
+}
+
+// Plays back how the territory around a tile has changed, using the snapshots of who owned what
+// that the background loop takes
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Watch how the map around a tile has changed")
+)]
+pub(crate) async fn timelapse(
+    ctx: Context<'_>,
+    #[description = "X coordinate of the centre tile"] x: i32,
+    #[description = "Y coordinate of the centre tile"] y: i32,
+    #[description = "How many hours back to start from"]
+    #[min = 1]
+    #[max = 72]
+    hours: u64,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+    let tag = db::users::get_user(ctx.author().id.to_string())
+        .await?
+        .faction;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let since = now.saturating_sub(hours.min(SNAPSHOT_HOURS) * 60 * 60);
+    let times = db::snapshots::get_times(since).await?;
+    if times.is_empty() {
+        ctx.say(
+            "There's no history to show yet. Who owns what is only recorded every half an hour.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let x_range = (x - DEFAULT_MAP_RADIUS, x + DEFAULT_MAP_RADIUS);
+    let y_range = (y - DEFAULT_MAP_RADIUS, y + DEFAULT_MAP_RADIUS);
+    // Only what the faction can see right now is played back, so old snapshots don't give away
+    // what is going on in the fog
+    let visibility = db::tiles::get_visibility(tag.clone()).await?;
+    let history = db::snapshots::get_many(x_range, y_range).await?;
+    let picked = pick_frames(&times, MAX_TIMELAPSE_FRAMES - 1);
+    let mut frames = Vec::new();
+    for (taken_at, owners) in picked.iter().zip(replay(&history, &picked)) {
+        let mut tiles = HashMap::new();
+        for ((x, y), faction) in owners {
+            if faction.is_empty() {
+                continue;
+            }
+            let snapshot = Snapshot {
+                x,
+                y,
+                faction,
+                taken_at: *taken_at,
+            };
+            tiles.insert((x, y), visibility.mask(snapshot.to_tile(), &tag));
+        }
+        frames.push((
+            *taken_at,
+            invert_y(fill_grid(x_range, y_range, tiles).await).await,
+        ));
+    }
+    // Finish on how things are right now
+    let mut current = HashMap::new();
+    for tile in tiles::get_many(x_range, y_range).await? {
+        if tile.faction.is_empty() {
+            continue;
+        }
+        let snapshot = Snapshot {
+            x: tile.x,
+            y: tile.y,
+            faction: tile.faction,
+            taken_at: now,
+        };
+        current.insert(
+            (snapshot.x, snapshot.y),
+            visibility.mask(snapshot.to_tile(), &tag),
+        );
+    }
+    frames.push((
+        now,
+        invert_y(fill_grid(x_range, y_range, current).await).await,
+    ));
+
+    let seen: Vec<Tile> = frames
+        .iter()
+        .flat_map(|(_, grid)| grid.iter().flatten().cloned())
+        .collect();
+    let colours = faction_colours(&seen).await?;
+    let data =
+        crate::image::draw_timelapse(&frames, &tag, &visibility, &colours, TIMELAPSE_TILE_PX, now)
+            .await;
+    let attachment = AttachmentType::Bytes {
+        data: std::borrow::Cow::Owned(data),
+        filename: "timelapse.gif".to_string(),
+    };
+    ctx.send(|b| {
+        b.attachment(attachment).content(format!(
+            "{} frames over the last {} hours",
+            frames.len(),
+            hours
+        ))
+    })
+    .await?;
+    Ok(())
+}
+
+// Looks up the colour of every faction that owns one of the tiles. Factions that have been wiped
+// out since are given the colour they would have had
+async fn faction_colours(tiles: &[Tile]) -> Result<HashMap<String, [u8; 3]>, Error> {
+    let mut colours = HashMap::new();
+    for tile in tiles {
+        if tile.faction.is_empty() || colours.contains_key(&tile.faction) {
+            continue;
+        }
+        let colour = if db::factions::faction_exists(tile.faction.clone()).await? {
+            db::factions::get_faction(tile.faction.clone())
+                .await?
+                .colour()
+        } else {
+            tag_colour(&tile.faction)
+        };
+        colours.insert(tile.faction.clone(), colour);
+    }
+    Ok(colours)
+}
+
+// Lays tiles out in a grid covering the whole area, with blank tiles wherever there isn't one
+async fn fill_grid(
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+    mut tiles: HashMap<(i32, i32), Tile>,
+) -> Vec<Vec<Tile>> {
+    let mut flat_tiles = Vec::new();
+    for x in x_range.0..=x_range.1 {
+        for y in y_range.0..=y_range.1 {
+            match tiles.remove(&(x, y)) {
+                Some(tile) => flat_tiles.push(tile),
+                None => flat_tiles.push(blank_tile(x, y).await),
+            }
+        }
+    }
+    split_tiles(flat_tiles, x_range.1 - x_range.0 + 1)
+}
+
+// This is the function that actually does all the work. Creates the image and the status message.
+// If history is given, troop movements from that many hours back are drawn on top. The map reaches
+// radius tiles out from the centre, and each tile is drawn tile_px pixels across
 pub async fn create_reply(
     x: i32,
     y: i32,
     faction: String,
+    history: Option<u64>,
+    radius: i32,
+    tile_px: u32,
 ) -> Result<(Cursor<Vec<u8>>, String), Error> {
     let mut dev_message = String::new();
-    let mut tiles: Vec<Vec<Tile>> = Vec::new();
-    let offset_base = crate::image::VIEW_DISTANCE / 2; // How far to go in each direction
-    let offset_x_min = x - offset_base;
-    let offset_x_max = x + offset_base;
-    let offset_y_min = y - offset_base;
-    let offset_y_max = y + offset_base;
-    let x_range = (offset_x_min, offset_x_max);
-    let y_range = (offset_y_min, offset_y_max);
-    let tiles_exist = tiles::all_exist(x_range, y_range).await?;
-    dev_message.push_str("Saved tiles: ");
-    // Check if all tiles exist
-    if tiles_exist {
-        dev_message.push_str("100%");
-        // If they do, get them from the database
-        let flat_tiles = tiles::get_many(x_range, y_range).await?;
-        println!("{} tiles", flat_tiles.len());
-        tiles = split_tiles(flat_tiles, crate::image::VIEW_DISTANCE);
-    } else {
-        // First see if any tiles exist
-        if tiles::any_exist(x_range, y_range).await? {
-            // If they don't, it's easier to just make a load of blank tiles
-            dev_message.push_str("0%");
-            let empty_tiles = tiles::blank_tile_range(x_range, y_range).await;
-            tiles = split_tiles(empty_tiles, crate::image::VIEW_DISTANCE);
-        } else {
-            // If only have some of the tiles, we need to get the ones that exist and
-            // generate the rest
-            let max_tiles = crate::image::VIEW_DISTANCE * crate::image::VIEW_DISTANCE;
-            let mut tiles_exist = 0;
-            let database = db::get_db().await?;
-            for x in offset_x_min..offset_x_max {
-                let mut tile_row: Vec<Tile> = Vec::new();
-                for y in offset_y_min..offset_y_max {
-                    let current_tile: Tile;
-                    if !tiles::internal_check_tile(&database, x, y).await? {
-                        current_tile = blank_tile(x, y).await;
-                    } else {
-                        tiles_exist += 1;
-                        current_tile = tiles::internal_get_tile(&database, x, y).await?;
-                    }
-                    tile_row.push(current_tile);
-                }
-                tiles.push(tile_row);
-            }
-            // If VIEW_DISTANCE is 10, then max_tiles will be 100 so it's pretty easy
-            // tho if the VIEW_DISTANCE is changed, this will still work
-            let percentage = (tiles_exist as f32 / max_tiles as f32) * 100.0;
-            dev_message.push_str(&format!("{}%", percentage));
-        }
+    let radius = radius.clamp(1, MAX_MAP_RADIUS); // How far to go in each direction
+    let size = radius * 2 + 1;
+    let x_range = (x - radius, x + radius);
+    let y_range = (y - radius, y + radius);
+    // Tiles out of the map can still change it if they let the faction see into it, so any change
+    // within scouting range counts
+    let revisions = render_cache::revisions(
+        (x_range.0 - SCOUT_SIGHT_RANGE, x_range.1 + SCOUT_SIGHT_RANGE),
+        (y_range.0 - SCOUT_SIGHT_RANGE, y_range.1 + SCOUT_SIGHT_RANGE),
+    );
+    let key = MapKey {
+        x,
+        y,
+        faction: faction.clone(),
+        history,
+        radius,
+        tile_px,
+    };
+    if let Some(data) = render_cache::get_map(&key, &revisions) {
+        dev_message.push_str("Served from cache");
+        return Ok((Cursor::new(data), dev_message));
+    }
+    // Get every saved tile in one go, then fill in the gaps with blank ones
+    let mut saved: HashMap<(i32, i32), Tile> = HashMap::new();
+    for tile in tiles::get_many(x_range, y_range).await? {
+        saved.insert((tile.x, tile.y), tile);
     }
-    // None of the percentage messages include a newline, so add it here
-    dev_message.push_str("\n");
+    let percentage = (saved.len() as f32 / (size * size) as f32) * 100.0;
+    dev_message.push_str(&format!("Saved tiles: {}%\n", percentage));
+    let mut tiles = fill_grid(x_range, y_range, saved).await;
     // Record the time it takes to generate the map
     let mut start = std::time::Instant::now();
-    tiles = invert_y(tiles).await;
-    tiles.remove(0);
+    // Only draw what the faction can actually see, or what it remembers of the rest
+    let mut visibility = db::tiles::get_visibility(faction.clone()).await?;
+    let mut intel = HashMap::new();
+    for sighting in db::intel::get_many(faction.clone(), x_range, y_range).await? {
+        intel.insert((sighting.x, sighting.y), sighting);
+    }
     for row in tiles.iter_mut() {
-        row.remove(row.len() - 1);
+        for tile in row.iter_mut() {
+            let mut masked = visibility.mask(tile.clone(), &faction);
+            if !visibility.can_see(tile.x, tile.y) {
+                if let Some(sighting) = intel.remove(&(tile.x, tile.y)) {
+                    visibility
+                        .remembered
+                        .insert((tile.x, tile.y), sighting.seen_at);
+                    masked = sighting.tile;
+                }
+            }
+            *tile = masked;
+        }
+    }
+    let mut movements = Vec::new();
+    if let Some(hours) = history {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let since = now.saturating_sub(hours.min(HISTORY_HOURS) * 60 * 60);
+        let recent = db::movements::get_recent(x_range, y_range, since).await?;
+        movements = visibility.known_movements(recent, &faction);
     }
+    // Every faction still on the map after the fog is drawn in its own colour
+    let visible: Vec<Tile> = tiles.iter().flatten().cloned().collect();
+    let colours = faction_colours(&visible).await?;
+    tiles = invert_y(tiles).await;
 
-    let image = crate::image::draw_map(&tiles, faction).await;
+    let image =
+        crate::image::draw_map(&tiles, faction, &visibility, &movements, &colours, tile_px).await;
     dev_message.push_str(&format!(
         "Map generated in {}ms",
         start.elapsed().as_millis()
     ));
     // Reset the timer and save the image
     start = std::time::Instant::now();
-    let mut cursor = Cursor::new(Vec::new());
-    image
-        .write_to(&mut cursor, image::ImageOutputFormat::Png)
-        .unwrap();
+    let (width, height) = image.dimensions();
+    let cursor = Cursor::new(crate::image::encode_png(image).await);
     dev_message.push_str(&format!(
         "\nImage encoded in {}ms",
         start.elapsed().as_millis()
     ));
-    dev_message.push_str(&format!(
-        "\nImage dimensions: {}x{}",
-        image.width(),
-        image.height()
-    ));
+    dev_message.push_str(&format!("\nImage dimensions: {}x{}", width, height));
+    render_cache::store_map(key, revisions, cursor.get_ref().clone());
     Ok((cursor, dev_message))
 }
\ No newline at end of file
@@ -1,7 +1,16 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use rand::Rng;
+
+use crate::config::get_config;
 use crate::conversions::string_to_unit;
 use crate::misc::log_command_used;
+use crate::pathfinding::{find_path, SEARCH_MARGIN};
+use crate::terrain::TerrainGenerator;
+use crate::types::diplomacy::Stance;
 use crate::types::permissions::Permissions;
-use crate::types::units::Unit;
+use crate::types::units::{Army, Unit};
 use crate::{db, Context, Error};
 
 #[poise::command(
@@ -39,7 +48,11 @@ pub(crate) async fn move_troops(
     let faction = db::users::get_user(ctx.author().id.to_string())
         .await?
         .faction;
-    let can_see = db::tiles::can_faction_see(to_x, to_y, faction.clone()).await?;
+    // Allied territory extends how far your troops can reach
+    let allies = db::relations::get_allies(faction.clone()).await?;
+    let mut sighted_by = allies.clone();
+    sighted_by.push(faction.clone());
+    let can_see = db::tiles::can_factions_see(to_x, to_y, sighted_by).await?;
     if !can_see {
         ctx.say("You can't move units into tiles beyond your reach!")
             .await?;
@@ -50,6 +63,10 @@ pub(crate) async fn move_troops(
         ctx.say(bad_tile_message).await?;
         return Ok(());
     }
+    if (from_x, from_y) == (to_x, to_y) {
+        ctx.say("Your units are already there!").await?;
+        return Ok(());
+    }
     let units = tile.units;
     let possible_unit = string_to_unit(&unit.to_lowercase()).await;
     if possible_unit.is_err() {
@@ -60,7 +77,7 @@ pub(crate) async fn move_troops(
         ctx.say(bad_tile_message).await?;
         return Ok(());
     }
-    let unit_amount = units.get(&possible_unit.unwrap());
+    let unit_amount = units.get(&possible_unit.clone().unwrap());
     if unit_amount.is_none() {
         // We already checked if the unit exists, so this should never happen
         // But just in case...
@@ -73,36 +90,95 @@ pub(crate) async fn move_troops(
         return Ok(());
     }
 
-    // Using pythagorean theorem to calculate distance
-    let distance_precise = (((from_x - to_x).pow(2) + (from_y - to_y).pow(2)) as f64).sqrt();
-    let distance = distance_precise.ceil() as u32;
-    let mut food_cost = 0;
-    for (unit, amount) in units.clone() {
-        if unit == Unit::Scout || unit == Unit::Soldier {
-            food_cost += amount;
-        } else if unit == Unit::Cavalry {
-            food_cost += amount * 2;
-        } else if unit == Unit::Ranger {
-            food_cost += amount * 3;
-        } else if unit == Unit::Knight {
-            food_cost += amount * 4;
-        }
+    // Moving into a tile held by another faction is an attack
+    let destination = db::tiles::get_tile(to_x, to_y).await?;
+    let destination_terrain = destination.terrain().data();
+    if !destination_terrain.passable {
+        ctx.say(format!(
+            "Your units can't cross {}!",
+            destination_terrain.name.to_lowercase()
+        ))
+        .await?;
+        return Ok(());
     }
-    food_cost *= distance;
+    let hostile = !destination.faction.is_empty() && destination.faction != faction;
+    let stance = db::relations::get_stance(faction.clone(), destination.faction.clone()).await?;
+    if hostile && stance == Stance::Alliance {
+        ctx.say("You can't station troops on your allies' tiles!")
+            .await?;
+        return Ok(());
+    }
+    if hostile && stance.is_peaceful() {
+        ctx.say(format!(
+            "You can't attack {} while you have {} with them!",
+            destination.faction,
+            stance.description()
+        ))
+        .await?;
+        return Ok(());
+    }
+    if hostile && !user.permitted(Permissions::War) {
+        ctx.say("You don't have permission to attack other factions!")
+            .await?;
+        return Ok(());
+    }
+
+    // March around anything in the way, rather than straight through it
+    let x_range = (
+        from_x.min(to_x) - SEARCH_MARGIN,
+        from_x.max(to_x) + SEARCH_MARGIN,
+    );
+    let y_range = (
+        from_y.min(to_y) - SEARCH_MARGIN,
+        from_y.max(to_y) + SEARCH_MARGIN,
+    );
+    let mut owners = HashMap::new();
+    for tile in db::tiles::get_many(x_range, y_range).await? {
+        owners.insert((tile.x, tile.y), tile.faction);
+    }
+    let terrain = TerrainGenerator::new(get_config().perlin_seed);
+    let path = find_path((from_x, from_y), (to_x, to_y), |x, y| {
+        let data = terrain.at(x, y).data();
+        if !data.passable {
+            return None;
+        }
+        // Only the tile being attacked can belong to someone you aren't allied with
+        let owner = owners.get(&(x, y)).cloned().unwrap_or_default();
+        let passable = owner.is_empty()
+            || owner == faction
+            || allies.contains(&owner)
+            || (x, y) == (to_x, to_y);
+        passable.then_some(data.movement_cost)
+    });
+    let path = match path {
+        Some(path) => path,
+        None => {
+            ctx.say("Your units can't find a way there!").await?;
+            return Ok(());
+        }
+    };
+
+    let moving_unit = possible_unit.unwrap();
+    let food_per_unit = match moving_unit {
+        Unit::Scout | Unit::Soldier => 1,
+        Unit::Cavalry => 2,
+        Unit::Ranger => 3,
+        Unit::Knight => 4,
+        Unit::Citizen => 0,
+    };
+    // Rough ground takes more effort to march over
+    let mut food_cost = (food_per_unit as f32 * amount as f32 * path.cost).ceil() as u32;
     if food_cost == 0 {
         food_cost = 1;
     }
-    let food = db::factions::get_faction(faction.clone())
-        .await?
-        .production
-        .food;
-    if food < food_cost as f32 {
+    let mut faction_data = db::factions::get_faction(faction.clone()).await?;
+    if faction_data.production.food < food_cost as f32 {
         ctx.send(|e| {
             e.embed(|e| {
                 e.title("You don't have enough food to move that many units!");
                 e.description(format!(
                     "You need {} food, but you only have {} food.",
-                    food_cost, food
+                    food_cost, faction_data.production.food
                 ));
                 e
             });
@@ -113,24 +189,13 @@ pub(crate) async fn move_troops(
     }
     // Passed all the checks, somehow
 
-    // Remove units from old tile
+    // The units leave straight away and spend the trip on the road
     let mut from_tile = db::tiles::get_tile(from_x, from_y).await?;
-    let mut to_tile = db::tiles::get_tile(to_x, to_y).await?;
-    for (unit, _) in from_tile.units.clone() {
-        from_tile
-            .units
-            .insert(unit.clone(), from_tile.units.get(&unit).unwrap() - amount);
-        if from_tile.units.get(&unit).unwrap() == &0 {
-            from_tile.units.remove(&unit);
-        }
-        let unit_exists = to_tile.units.contains_key(&unit);
-
-        let new_amount = if unit_exists {
-            to_tile.units.get(&unit).unwrap() + amount
-        } else {
-            amount
-        };
-        to_tile.units.insert(unit.clone(), new_amount);
+    let remaining = from_tile.units.get(&moving_unit).unwrap() - amount;
+    if remaining == 0 {
+        from_tile.units.remove(&moving_unit);
+    } else {
+        from_tile.units.insert(moving_unit.clone(), remaining);
     }
     let mut save_result = db::tiles::set_tile(from_tile).await;
     if save_result.is_err() {
@@ -138,40 +203,60 @@ pub(crate) async fn move_troops(
             .await?;
         return Ok(());
     }
-    to_tile.faction = faction.clone();
-    to_tile.occupied = true;
-    save_result = db::tiles::set_tile(to_tile).await;
+    faction_data.production.food -= food_cost as f32;
+    save_result = db::factions::save_faction(faction_data).await;
     if save_result.is_err() {
-        ctx.say("Something went wrong while saving the tile!")
+        ctx.say("Something went wrong while saving the faction!")
             .await?;
         return Ok(());
     }
-    let current_food = db::factions::get_faction(faction.clone())
-        .await?
-        .production
-        .food;
-    let new_food = current_food - food_cost as f32;
-    let mut faction = db::factions::get_faction(faction.clone()).await?;
-    faction.production.food = new_food;
-    save_result = db::factions::save_faction(faction).await;
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut units = HashMap::new();
+    units.insert(moving_unit, amount);
+    let mut army = Army {
+        id: format!("{:08x}", rand::rngs::OsRng.gen::<u32>()),
+        faction: faction.clone(),
+        units,
+        route: path.tiles,
+        ordered_by: ctx.author().id.to_string(),
+        ..Default::default()
+    };
+    army.next_step_at = now + army.step_time(&terrain);
+    army.arrives_at = now + army.remaining_time(&terrain);
+    let arrives_at = army.arrives_at;
+    let steps = army.route.len() - 1;
+    save_result = db::armies::add_army(army).await;
     if save_result.is_err() {
-        ctx.say("Something went wrong while saving the faction!")
+        ctx.say("Something went wrong while saving the army!")
             .await?;
         return Ok(());
     }
     ctx.send(|e| {
         e.embed(|e| {
-            e.title("Moved units!");
+            e.title("Your army is on the march!");
             e.description(format!(
-                "Moved {} {} from {} {} to {} {}. Food cost: {}",
-                amount, unit, from_x, from_y, to_x, to_y, food_cost
+                "{} {} set off from {}, {} towards {}, {}. They will arrive <t:{}:R> after crossing {} tiles. Food cost: {}",
+                amount, unit, from_x, from_y, to_x, to_y, arrives_at, steps, food_cost
             ));
+            if hostile {
+                e.field(
+                    "Attack",
+                    format!(
+                        "They will attack {} when they get there. You'll get a DM with the result.",
+                        destination.faction
+                    ),
+                    false,
+                );
+            }
             e
         });
         e
     })
-    .await
-    .unwrap();
+    .await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -18,41 +18,84 @@ pub(crate) async fn info(ctx: Context<'_>, x: i32, y: i32) -> Result<(), Error>
     let faction = db::users::get_user(ctx.author().id.to_string())
         .await?
         .faction;
-    let can_see = db::tiles::can_faction_see(x, y, faction).await?;
-    if !can_see {
-        ctx.say(cant_see_message).await?;
-        return Ok(());
-    }
-    let tile = if db::tiles::check_tile(x, y).await? {
-        db::tiles::get_tile(x, y).await?
+    let visibility = db::tiles::get_visibility(faction.clone()).await?;
+    // Tiles out of sight are shown as they were last seen, if they ever were
+    let mut seen_at = None;
+    let tile = if visibility.can_see(x, y) {
+        if db::tiles::check_tile(x, y).await? {
+            db::tiles::get_tile(x, y).await?
+        } else {
+            db::tiles::blank_tile(x, y).await
+        }
     } else {
-        db::tiles::blank_tile(x, y).await
+        match db::intel::get_intel(faction.clone(), x, y).await? {
+            Some(intel) => {
+                seen_at = Some(intel.seen_at);
+                intel.tile
+            }
+            None => {
+                ctx.say(cant_see_message).await?;
+                return Ok(());
+            }
+        }
     };
     let mut owner = "None".to_string();
-    if tile.faction != "" {
+    if !tile.faction.is_empty() && !db::factions::faction_exists(tile.faction.clone()).await? {
+        // They have been wiped out since
+        owner = tile.faction.clone();
+    } else if tile.faction != "" {
         owner = db::factions::get_faction(tile.faction.clone())
             .await?
             .name
             .clone();
     }
+    let terrain = tile.terrain().data();
+    let deposit = match tile.deposit() {
+        Some(deposit) => {
+            let data = deposit.data();
+            format!("{}: {}", data.name, data.description)
+        }
+        None => "None".to_string(),
+    };
     let mut buildings = "".to_string();
     for (k, v) in tile.buildings {
-        buildings.push_str(format!("{}: {}\n", k.data().name, v).as_str());
+        buildings
+            .push_str(format!("{}: {} ({})\n", k.data().name, v.total(), v.describe()).as_str());
     }
     if buildings == "" {
         buildings = "None".to_string();
     }
+    // Enemy troops can only be counted up close, or by scouts. Old sightings only have troops in
+    // them if they were counted at the time
+    let scouted = match seen_at {
+        Some(_) => !tile.units.is_empty(),
+        None => tile.faction == faction || visibility.can_scout(x, y),
+    };
     let mut units = "".to_string();
     for (k, v) in tile.units {
         units.push_str(format!("{}: {}\n", k.data().name, v).as_str());
     }
-    if units == "" {
+    if !scouted {
+        units = "Unknown. Send scouts to find out".to_string();
+    } else if units == "" {
         units = "None".to_string();
     }
     ctx.send(|b| {
         b.embed(|e| {
-            e.title(format!("{}, {}", x, y))
-                .field("Owner", owner, false)
+            e.title(format!("{}, {}", x, y));
+            if let Some(seen_at) = seen_at {
+                e.description(format!(
+                    "You can't see this tile right now. This is how it looked <t:{}:R>.",
+                    seen_at
+                ));
+            }
+            e.field("Owner", owner, false)
+                .field(
+                    "Terrain",
+                    format!("{}: {}", terrain.name, terrain.description),
+                    false,
+                )
+                .field("Deposit", deposit, false)
                 .field("Buildings", buildings, false)
                 .field("Units", units, false)
         })
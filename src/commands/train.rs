@@ -0,0 +1,117 @@
+use std::time::SystemTime;
+
+use rand::Rng;
+
+use crate::conversions::string_to_unit;
+use crate::misc::log_command_used;
+use crate::types::buildings::Building;
+use crate::types::permissions::Permissions;
+use crate::types::units::{TrainingOrder, Unit};
+use crate::{db, Context, Error};
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Train units at a barracks")
+)]
+pub(crate) async fn train(
+    ctx: Context<'_>,
+    #[description = "The name of the unit you want to train. Check \"/explain units\" for a list of units"]
+    unit: String,
+    #[description = "The amount of units you want to train"] amount: u32,
+    #[description = "The x coordinate of the tile with the barracks"] x: i32,
+    #[description = "The y coordinate of the tile with the barracks"] y: i32,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if user.faction.is_empty() {
+        ctx.say("You are not in a faction!").await?;
+        return Ok(());
+    }
+    if !user.permitted(Permissions::Build) {
+        ctx.say("You don't have permission to train units!").await?;
+        return Ok(());
+    }
+    if amount == 0 {
+        ctx.say("You can't train 0 units!").await?;
+        return Ok(());
+    }
+    let unit = match string_to_unit(&unit.to_lowercase()).await {
+        Ok(unit) => unit,
+        Err(_) => {
+            ctx.say("That's not a valid unit!").await?;
+            return Ok(());
+        }
+    };
+    if unit == Unit::Citizen {
+        ctx.say("Citizens can't be trained, they grow with your population!")
+            .await?;
+        return Ok(());
+    }
+    let tile = db::tiles::get_tile(x, y).await?;
+    if tile.faction != user.faction {
+        ctx.say("You can only train units on your own tiles!")
+            .await?;
+        return Ok(());
+    }
+    let barracks = tile
+        .buildings
+        .get(&Building::Barracks)
+        .cloned()
+        .unwrap_or_default();
+    if barracks.total() == 0 {
+        ctx.say("You need a barracks on that tile to train units!")
+            .await?;
+        return Ok(());
+    }
+
+    let details = unit.data();
+    let (cost, metal, food) = unit.training_cost(amount);
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    if let Some(refusal) = unit.refuse_training(amount, &faction.production) {
+        ctx.say(refusal).await?;
+        return Ok(());
+    }
+    if let Some(refusal) = user.refuse_spending(cost, faction.production.money) {
+        ctx.say(refusal).await?;
+        return Ok(());
+    }
+
+    faction.production.money -= cost;
+    faction.production.metal -= metal;
+    faction.production.food -= food;
+    faction.production.population -= amount as f64;
+    db::factions::save_faction(faction).await?;
+
+    // More barracks on the tile means more units can be trained at once, and upgraded barracks
+    // train faster
+    let duration = ((details.train_time * amount as u64) as f64 / barracks.output()) as u64;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let order = TrainingOrder {
+        id: format!("{:08x}", rand::rngs::OsRng.gen::<u32>()),
+        faction: user.faction,
+        x,
+        y,
+        unit,
+        amount,
+        ready_at: now + duration,
+        ordered_by: user.uuid,
+    };
+    db::training::add_order(order).await?;
+    ctx.say(format!(
+        "Training {} {}! They will be ready <t:{}:R>.",
+        amount,
+        details.name,
+        now + duration
+    ))
+    .await?;
+    Ok(())
+}
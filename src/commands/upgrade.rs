@@ -0,0 +1,125 @@
+use crate::conversions::string_to_building;
+use crate::misc::log_command_used;
+use crate::types::buildings::{upgrade_cost_multiplier, Building, MAX_LEVEL};
+use crate::types::permissions::Permissions;
+use crate::{db, Context, Error};
+
+#[poise::command(
+slash_command,
+// ephemeral,
+description_localized("en-US", "Upgrade buildings so they produce more")
+)]
+pub(crate) async fn upgrade(
+    ctx: Context<'_>,
+    #[description_localized("en-US", "The building to upgrade")] building: String,
+    #[description_localized("en-US", "The amount of buildings to upgrade")] amount: u32,
+    #[description_localized("en-US", "The x coordinate of the tile to upgrade on")] x: i32,
+    #[description_localized("en-US", "The y coordinate of the tile to upgrade on")] y: i32,
+) -> Result<(), Error> {
+    log_command_used(ctx).await;
+    if !db::users::user_exists(ctx.author().id.to_string()).await? {
+        ctx.say("You need to register first!\nUse `/register` to join!")
+            .await?;
+        return Ok(());
+    }
+    let user = db::users::get_user(ctx.author().id.to_string()).await?;
+    if !user.permitted(Permissions::Build) {
+        ctx.say("You don't have permission to upgrade buildings!")
+            .await?;
+        return Ok(());
+    }
+    if amount == 0 {
+        ctx.say("You can't upgrade nothing!").await?;
+        return Ok(());
+    }
+    let building = match string_to_building(&building.to_lowercase()).await {
+        Ok(building) => building,
+        Err(_) => {
+            ctx.say("That's not a valid building!").await?;
+            return Ok(());
+        }
+    };
+    if building == Building::Capital {
+        ctx.say("Your capital can't be upgraded!").await?;
+        return Ok(());
+    }
+    let mut tile = db::tiles::get_tile(x, y).await?;
+    if user.faction.is_empty() || tile.faction != user.faction {
+        ctx.say("You can't upgrade buildings on tiles that aren't yours!")
+            .await?;
+        return Ok(());
+    }
+    let details = building.data();
+    let mut levels = tile.buildings.get(&building).cloned().unwrap_or_default();
+    let upgradable = levels.total() - levels.at(MAX_LEVEL);
+    if upgradable < amount {
+        ctx.say(format!(
+            "There are only {} {}s on that tile that can still be upgraded!",
+            upgradable, details.name
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    // The lowest level buildings are upgraded first, and each level costs more than the last
+    // Buildings only go up one level at a time, so count from how things were before any moved up
+    let before = levels.clone();
+    let mut left = amount;
+    let mut multiplier = 0;
+    for level in 1..MAX_LEVEL {
+        let upgraded = left.min(before.at(level));
+        levels.0[level as usize - 1] -= upgraded;
+        levels.add(level + 1, upgraded);
+        multiplier += upgraded as i32 * upgrade_cost_multiplier(level + 1);
+        left -= upgraded;
+    }
+    let cost = details.cost * multiplier;
+    let wood = details.wood * multiplier;
+    let metal = details.metal * multiplier;
+    let mut faction = db::factions::get_faction(user.faction.clone()).await?;
+    if cost > faction.production.money.floor() as i32 {
+        ctx.say(format!(
+            "You don't have enough money to upgrade that many! You need ${}.",
+            cost
+        ))
+        .await?;
+        return Ok(());
+    }
+    if let Some(refusal) = user.refuse_spending(cost as f32, faction.production.money) {
+        ctx.say(refusal).await?;
+        return Ok(());
+    }
+    if wood > faction.production.wood.floor() as i32 {
+        ctx.say(format!(
+            "You don't have enough wood to upgrade that many! You need {}.",
+            wood
+        ))
+        .await?;
+        return Ok(());
+    }
+    if metal > faction.production.metal.floor() as i32 {
+        ctx.say(format!(
+            "You don't have enough metal to upgrade that many! You need {}.",
+            metal
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let total = levels.total();
+    let description = levels.describe();
+    tile.buildings.insert(building, levels);
+    db::tiles::set_tile(tile).await?;
+    faction.production.money -= cost as f32;
+    faction.production.wood -= wood as f32;
+    faction.production.metal -= metal as f32;
+    // Don't wait for the background loop, the upgraded buildings are working already
+    db::build_faction_production(faction).await?;
+
+    ctx.say(format!(
+        "You upgraded {} {}s for ${}, {} wood and {} metal! The tile now has {} {}s ({}).",
+        amount, details.name, cost, wood, metal, total, details.name, description
+    ))
+    .await?;
+    Ok(())
+}
@@ -8,16 +8,24 @@ use tokio::join;
 use crate::background::background_loop;
 use commands::map::map;
 
+use crate::commands::armies::armies;
 use crate::commands::build::build;
+use crate::commands::claim::claim;
+use crate::commands::demolish::demolish;
 use crate::commands::dev::dev;
+use crate::commands::diplomacy::diplomacy;
 use crate::commands::faction::faction;
 use crate::commands::help::{explain, guide};
 use crate::commands::r#move::move_troops;
 use crate::commands::tile::tile;
+use crate::commands::train::train;
+use crate::commands::upgrade::upgrade;
 use crate::commands::user::register;
 
 #[path = "utils/background.rs"]
 mod background;
+#[path = "utils/combat.rs"]
+mod combat;
 mod commands;
 #[path = "utils/config.rs"]
 mod config;
@@ -29,13 +37,25 @@ mod db;
 mod image;
 #[path = "utils/misc_utils.rs"]
 mod misc;
+#[path = "utils/pathfinding.rs"]
+mod pathfinding;
+#[path = "utils/render_cache.rs"]
+mod render_cache;
+#[path = "utils/terrain.rs"]
+mod terrain;
 mod tests;
 #[path = "utils/types.rs"]
 mod types;
+#[path = "utils/vision.rs"]
+mod vision;
 #[derive(RustEmbed)]
 #[folder = "src/help/topics/"]
 struct HelpTopics;
 
+#[derive(RustEmbed)]
+#[folder = "src/icons/"]
+struct MapIcons;
+
 pub struct Data {}
 
 // User data, which is stored and accessible in all command invocations
@@ -48,6 +68,15 @@ const GUIDE_MESSAGE: &str = include_str!("help/guide.txt");
 async fn main() {
     pretty_env_logger::init();
     info!("Starting bot...");
+    let migrated = db::tiles::migrate_building_levels()
+        .await
+        .expect("Failed to migrate building levels");
+    if migrated > 0 {
+        info!("Migrated buildings on {} tiles to use levels", migrated);
+    }
+    db::snapshots::create_indexes()
+        .await
+        .expect("Failed to create snapshot indexes");
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
             commands: vec![
@@ -57,8 +86,14 @@ async fn main() {
                 explain(),
                 tile(),
                 move_troops(),
+                armies(),
                 build(),
+                claim(),
+                demolish(),
+                upgrade(),
+                train(),
                 faction(),
+                diplomacy(),
                 register(),
             ], // for some reason intellij is complaining about this line, but it works fine
             on_error: |error| {
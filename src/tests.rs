@@ -1,7 +1,34 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use mongodb::bson::{doc, from_document};
+    use noise::{Clamp, NoiseFn, OpenSimplex};
+
+    use crate::combat::resolve_battle;
+    use crate::conversions::{hsv_to_rgb, string_to_colour};
+    use crate::db::cleaners::is_abandoned;
+    use crate::db::construction::reschedule;
+    use crate::db::snapshots::{ownership_changes, replay};
     use crate::db::tiles::blank_tile;
-    use crate::image::hsv_to_rgb;
+    use crate::db::{desert, target_happiness, tick_production};
+    use crate::image::{
+        arrow_shape, combine_movements, draw_background, legend_layout, map_tile_px,
+        overview_range, pick_frames, render_map, short_count,
+    };
+    use crate::pathfinding::find_path;
+    use crate::render_cache;
+    use crate::render_cache::{region_of, MapKey, Shade};
+    use crate::terrain::TerrainGenerator;
+    use crate::types::buildings::{Building, ConstructionJob, Levels};
+    use crate::types::diplomacy::{Relation, Stance};
+    use crate::types::factions::{tag_colour, Faction, Production, DEFAULT_TAX_RATE};
+    use crate::types::map::{Snapshot, Tile};
+    use crate::types::permissions::Permissions;
+    use crate::types::terrain::{Deposit, Terrain};
+    use crate::types::units::{Army, Movement, Unit};
+    use crate::types::users::User;
+    use crate::vision::{format_age, Visibility, SCOUT_SIGHT_RANGE, SIGHT_RANGE};
 
     #[test]
     fn rgb() {
@@ -18,5 +45,736 @@ mod tests {
         assert_eq!(g, 0);
         assert_eq!(b, 0);
     }
-}
 
+    #[test]
+    fn knights_beat_soldiers() {
+        let attackers = HashMap::from([(Unit::Knight, 10)]);
+        let defenders = HashMap::from([(Unit::Soldier, 10)]);
+        let result = resolve_battle(&attackers, &defenders);
+        assert!(result.attacker_won);
+        assert_eq!(result.defender_losses.get(&Unit::Soldier), Some(&10));
+        assert!(result.defender_survivors.is_empty());
+        assert!(result.attacker_survivors.get(&Unit::Knight).unwrap() > &0);
+    }
+
+    #[test]
+    fn citizens_cant_attack() {
+        let attackers = HashMap::from([(Unit::Citizen, 100)]);
+        let defenders = HashMap::from([(Unit::Soldier, 1)]);
+        let result = resolve_battle(&attackers, &defenders);
+        assert!(!result.attacker_won);
+        assert_eq!(result.attacker_losses.get(&Unit::Citizen), Some(&100));
+        assert_eq!(result.defender_survivors.get(&Unit::Soldier), Some(&1));
+    }
+
+    #[test]
+    fn empty_tiles_fall() {
+        let attackers = HashMap::from([(Unit::Scout, 1)]);
+        let result = resolve_battle(&attackers, &HashMap::new());
+        assert!(result.attacker_won);
+        assert!(result.attacker_losses.is_empty());
+    }
+
+    #[test]
+    fn relations_are_symmetric() {
+        let first = Relation::new("ABCD", "WXYZ");
+        let second = Relation::new("WXYZ", "ABCD");
+        assert_eq!(first.faction_a, second.faction_a);
+        assert_eq!(first.faction_b, second.faction_b);
+        assert_eq!(first.other("ABCD"), "WXYZ");
+        assert_eq!(first.stance, Stance::Neutral);
+        assert!(!Stance::War.is_peaceful());
+        assert!(Stance::NonAggression.is_peaceful());
+    }
+
+    #[test]
+    fn population_stays_under_cap() {
+        let production = Production {
+            population: 95.0,
+            population_per_second: 1.0,
+            population_cap: 100,
+            food: 500.0,
+            happiness: 50.0,
+            tax_rate: DEFAULT_TAX_RATE,
+            ..Default::default()
+        };
+        let production = tick_production(production, 60.0);
+        assert_eq!(production.population, 100.0);
+    }
+
+    #[test]
+    fn starvation_shrinks_population() {
+        let production = Production {
+            population: 100.0,
+            population_per_second: 1.0,
+            population_cap: 200,
+            food: 10.0,
+            food_per_second: -1.0,
+            happiness: 50.0,
+            tax_rate: DEFAULT_TAX_RATE,
+            ..Default::default()
+        };
+        let production = tick_production(production, 110.0);
+        assert_eq!(production.food, 0.0);
+        assert!((production.population - 90.0).abs() < 0.001);
+        assert!(production.happiness < 50.0);
+    }
+
+    #[test]
+    fn high_taxes_are_unpopular() {
+        let fair = Production {
+            population: 50.0,
+            population_cap: 100,
+            food: 100.0,
+            tax_rate: DEFAULT_TAX_RATE,
+            ..Default::default()
+        };
+        let greedy = Production {
+            tax_rate: 80.0,
+            ..fair
+        };
+        assert!(target_happiness(&greedy) < target_happiness(&fair));
+    }
+
+    #[test]
+    fn no_money_cap_lifts_spending_limit() {
+        let mut user = User::default();
+        assert_eq!(user.spending_limit(1000.0), 250.0);
+        assert!(user.refuse_spending(300.0, 1000.0).is_some());
+        user.permissions.push(Permissions::NoMoneyCap);
+        assert_eq!(user.spending_limit(1000.0), 1000.0);
+        assert!(user.refuse_spending(300.0, 1000.0).is_none());
+    }
+
+    #[test]
+    fn training_needs_resources_and_people() {
+        let soldier = Unit::Soldier.data();
+        let (cost, metal, food) = Unit::Soldier.training_cost(4);
+        assert_eq!(cost, soldier.cost as f32 * 4.0);
+        assert_eq!(metal, soldier.metal as f32 * 4.0);
+        assert_eq!(food, soldier.food as f32 * 4.0);
+        let mut production = Production {
+            money: cost,
+            metal,
+            food,
+            population: 4.0,
+            ..Default::default()
+        };
+        assert!(Unit::Soldier.refuse_training(4, &production).is_none());
+        production.population = 3.9;
+        assert!(Unit::Soldier.refuse_training(4, &production).is_some());
+        production.population = 10.0;
+        production.money -= 1.0;
+        assert!(Unit::Soldier.refuse_training(4, &production).is_some());
+    }
+
+    #[test]
+    fn only_paid_units_desert() {
+        let units = HashMap::from([(Unit::Citizen, 100), (Unit::Soldier, 10)]);
+        let mut rng = rand::rngs::mock::StepRng::new(0, 0);
+        let remaining = desert(&units, 0.5, &mut rng);
+        assert_eq!(remaining.get(&Unit::Citizen), Some(&100));
+        assert_eq!(remaining.get(&Unit::Soldier), Some(&5));
+    }
+
+    fn job(id: &str, started_at: u64, ready_at: u64) -> ConstructionJob {
+        ConstructionJob {
+            id: id.to_string(),
+            faction: "TEST".to_string(),
+            x: 0,
+            y: 0,
+            building: Building::Farm,
+            amount: 1,
+            started_at,
+            ready_at,
+            ordered_by: "".to_string(),
+            cost: 0,
+            wood: 0,
+            metal: 0,
+        }
+    }
+
+    #[test]
+    fn cancelled_jobs_free_up_the_queue() {
+        // The job that was running until 100 was cancelled, so everything behind it moves up
+        let jobs = vec![job("a", 100, 200), job("b", 200, 260), job("c", 260, 300)];
+        let scheduled = reschedule(jobs.clone(), 50);
+        assert_eq!((scheduled[0].started_at, scheduled[0].ready_at), (50, 150));
+        assert_eq!((scheduled[1].started_at, scheduled[1].ready_at), (150, 210));
+        assert_eq!((scheduled[2].started_at, scheduled[2].ready_at), (210, 250));
+        // Work that has already started keeps going as it was
+        let scheduled = reschedule(jobs, 120);
+        assert_eq!((scheduled[0].started_at, scheduled[0].ready_at), (100, 200));
+        assert_eq!((scheduled[1].started_at, scheduled[1].ready_at), (200, 260));
+    }
+
+    #[test]
+    fn started_jobs_refund_what_is_left() {
+        let queued = job("a", 100, 200);
+        assert_eq!(queued.refund_share(50), 1.0);
+        assert_eq!(queued.refund_share(150), 0.5);
+        assert_eq!(queued.refund_share(200), 0.0);
+    }
+
+    #[test]
+    fn orders_pay_for_every_building() {
+        let farm = Building::Farm.data();
+        assert_eq!(
+            Building::Farm.cost_of(3),
+            (farm.cost * 3, farm.wood * 3, farm.metal * 3)
+        );
+    }
+
+    #[test]
+    fn demolishing_refunds_half() {
+        let farm = Building::Farm.data();
+        let (money, wood, metal) = Building::Farm.salvage(2).unwrap();
+        assert_eq!(money, farm.cost as f32);
+        assert_eq!(wood, farm.wood as f32);
+        assert_eq!(metal, farm.metal as f32);
+        // Capitals can never be torn down
+        assert!(Building::Capital.salvage(1).is_none());
+    }
+
+    #[test]
+    fn old_building_counts_become_level_one() {
+        let document = doc! {
+            "occupied": true,
+            "faction": "TEST",
+            "buildings": {"Farm": 3, "Mill": [1, 2]},
+            "units": {},
+            "x": 0,
+            "y": 0,
+        };
+        let tile: Tile = from_document(document).unwrap();
+        assert_eq!(tile.buildings[&Building::Farm], Levels(vec![3]));
+        assert_eq!(tile.buildings[&Building::Mill], Levels(vec![1, 2]));
+    }
+
+    #[test]
+    fn upgraded_buildings_produce_more() {
+        let mut levels = Levels::new(1, 2);
+        levels.add(3, 1);
+        assert_eq!(levels.total(), 3);
+        assert_eq!(levels.output(), 4.0);
+        // Demolishing takes the worst buildings first
+        levels.remove(2);
+        assert_eq!(levels.at(1), 0);
+        assert_eq!(levels.at(3), 1);
+    }
+
+    #[test]
+    fn terrain_comes_from_the_seed() {
+        let first = TerrainGenerator::new(42);
+        let second = TerrainGenerator::new(42);
+        let mut seen = Vec::new();
+        for x in -30..30 {
+            for y in -30..30 {
+                let terrain = first.at(x, y);
+                assert_eq!(terrain, second.at(x, y));
+                if !seen.contains(&terrain) {
+                    seen.push(terrain);
+                }
+            }
+        }
+        // A decent chunk of the map should have a bit of everything
+        assert_eq!(seen.len(), 5);
+    }
+
+    #[test]
+    fn terrain_limits_buildings() {
+        assert!(!Terrain::Mountains.allows(&Building::Farm));
+        assert!(Terrain::Mountains.allows(&Building::Blacksmith));
+        assert!(Terrain::Forest.allows(&Building::Mill));
+        assert!(!Terrain::Plains.allows(&Building::Mill));
+        assert!(!Terrain::Water.allows(&Building::House));
+    }
+
+    #[test]
+    fn deposits_are_rare_and_match_the_terrain() {
+        let generator = TerrainGenerator::new(42);
+        let mut found = 0;
+        for x in -30..30 {
+            for y in -30..30 {
+                if let Some(deposit) = generator.deposit_at(x, y) {
+                    found += 1;
+                    let expected = match generator.at(x, y) {
+                        Terrain::Plains => Deposit::FertileSoil,
+                        Terrain::Forest => Deposit::Timber,
+                        Terrain::Hills => Deposit::Stone,
+                        Terrain::Mountains => Deposit::Iron,
+                        Terrain::Water => {
+                            panic!("Found {:?} in the water at {}, {}", deposit, x, y)
+                        }
+                    };
+                    assert_eq!(deposit, expected);
+                }
+            }
+        }
+        assert!(found > 0);
+        assert!(found < 60 * 60 / 5);
+    }
+
+    #[test]
+    fn claimed_tiles_survive_cleaning() {
+        let mut tile = Tile {
+            occupied: true,
+            faction: "TEST".to_string(),
+            x: 3,
+            y: 4,
+            ..Default::default()
+        };
+        // Troops marched through and left, so nobody owns it any more
+        assert!(is_abandoned(&tile));
+        tile.claimed = true;
+        assert!(!is_abandoned(&tile));
+        tile.faction = "".to_string();
+        assert!(is_abandoned(&tile));
+    }
+
+    #[test]
+    fn claims_must_touch_territory() {
+        let tile = Tile {
+            x: 3,
+            y: 4,
+            ..Default::default()
+        };
+        assert!(tile.is_adjacent(4, 5));
+        assert!(tile.is_adjacent(2, 4));
+        assert!(!tile.is_adjacent(3, 4));
+        assert!(!tile.is_adjacent(5, 4));
+    }
+
+    #[test]
+    fn scouts_see_further() {
+        let mut outpost = Tile {
+            occupied: true,
+            faction: "TEST".to_string(),
+            ..Default::default()
+        };
+        let visibility = Visibility::from_territory(&[outpost.clone()]);
+        assert!(visibility.can_see(SIGHT_RANGE, 0));
+        assert!(!visibility.can_see(SIGHT_RANGE + 1, 0));
+        assert!(!visibility.can_scout(2, 0));
+
+        outpost.units.insert(Unit::Scout, 1);
+        let visibility = Visibility::from_territory(&[outpost]);
+        assert!(visibility.can_see(SCOUT_SIGHT_RANGE, 0));
+        assert!(visibility.can_scout(SCOUT_SIGHT_RANGE, 0));
+    }
+
+    #[test]
+    fn fog_hides_enemies() {
+        let home = Tile {
+            occupied: true,
+            faction: "TEST".to_string(),
+            ..Default::default()
+        };
+        let visibility = Visibility::from_territory(&[home]);
+        let mut enemy = Tile {
+            occupied: true,
+            faction: "ENEMY".to_string(),
+            x: 3,
+            ..Default::default()
+        };
+        enemy.units.insert(Unit::Knight, 10);
+        // Close enough to see who owns it, but not to count the troops
+        let masked = visibility.mask(enemy.clone(), "TEST");
+        assert_eq!(masked.faction, "ENEMY");
+        assert!(masked.units.is_empty());
+        // Too far away to see at all
+        enemy.x = SIGHT_RANGE + 1;
+        let masked = visibility.mask(enemy, "TEST");
+        assert!(masked.faction.is_empty());
+    }
+
+    #[test]
+    fn intel_ages_fit_on_a_tile() {
+        assert_eq!(format_age(30), "now");
+        assert_eq!(format_age(5 * 60), "5m");
+        assert_eq!(format_age(3 * 60 * 60 + 59), "3h");
+        assert_eq!(format_age(2 * 24 * 60 * 60), "2d");
+    }
+
+    #[test]
+    fn paths_go_around_water() {
+        // A wall of water with a single gap at the top
+        let path = find_path((0, 0), (4, 0), |x, y| {
+            if x == 2 && y != 3 {
+                None
+            } else {
+                Some(1.0)
+            }
+        })
+        .unwrap();
+        assert_eq!(path.tiles.first(), Some(&(0, 0)));
+        assert_eq!(path.tiles.last(), Some(&(4, 0)));
+        assert!(path.tiles.contains(&(2, 3)));
+        for step in path.tiles.windows(2) {
+            assert!((step[0].0 - step[1].0).abs() <= 1 && (step[0].1 - step[1].1).abs() <= 1);
+        }
+        // Sealed off completely
+        assert!(find_path((0, 0), (4, 0), |x, _| (x != 2).then_some(1.0)).is_none());
+    }
+
+    #[test]
+    fn paths_avoid_rough_ground() {
+        // Mountains straight ahead, open ground a short detour away
+        let path = find_path((0, 0), (6, 0), |x, y| {
+            if y == 0 && (1..=5).contains(&x) {
+                Some(2.5)
+            } else {
+                Some(1.0)
+            }
+        })
+        .unwrap();
+        assert!(path.tiles.iter().all(|&(x, y)| y != 0 || x == 0 || x == 6));
+        assert!(path.cost < 2.5 * 5.0 + 1.0);
+    }
+
+    #[test]
+    fn armies_meet_when_crossing() {
+        let marching = Army {
+            units: HashMap::from([(Unit::Soldier, 5)]),
+            route: vec![(0, 0), (1, 0), (2, 0)],
+            step: 1,
+            ..Default::default()
+        };
+        // Coming the other way and swapped tiles with it
+        let mut other = Army {
+            units: HashMap::from([(Unit::Knight, 5)]),
+            route: vec![(1, 0), (0, 0)],
+            step: 1,
+            ..Default::default()
+        };
+        assert!(marching.meets(&other));
+        // Off on its own way
+        other.route = vec![(0, 5), (0, 6)];
+        assert!(!marching.meets(&other));
+        // Armies march at the pace of their slowest unit
+        let mixed = Army {
+            units: HashMap::from([(Unit::Cavalry, 5), (Unit::Knight, 1)]),
+            ..Default::default()
+        };
+        assert_eq!(mixed.march_time(), Unit::Knight.data().march_time);
+    }
+
+    #[test]
+    fn armies_turn_back_the_way_they_came() {
+        let mut army = Army {
+            units: HashMap::from([(Unit::Soldier, 5)]),
+            route: vec![(0, 0), (1, 0), (2, 0), (3, 0)],
+            step: 2,
+            ..Default::default()
+        };
+        army.turn_back();
+        assert_eq!(army.route, vec![(2, 0), (1, 0), (0, 0)]);
+        assert_eq!(army.position(), (2, 0));
+        assert!(army.returning);
+        let terrain = TerrainGenerator::new(1);
+        assert!(army.remaining_time(&terrain) >= 2 * Unit::Soldier.data().march_time);
+    }
+
+    #[test]
+    fn only_known_movements_are_shown() {
+        let home = Tile {
+            occupied: true,
+            faction: "TEST".to_string(),
+            ..Default::default()
+        };
+        let visibility = Visibility::from_territory(&[home]);
+        let movement = |faction: &str, to_x: i32| Movement {
+            faction: faction.to_string(),
+            from_x: 50,
+            from_y: 0,
+            to_x,
+            to_y: 0,
+            unit: Unit::Soldier,
+            amount: 5,
+            moved_at: 0,
+        };
+        let known = visibility.known_movements(
+            vec![
+                movement("TEST", 50),
+                movement("ENEMY", 1),
+                movement("ENEMY", SIGHT_RANGE + 1),
+            ],
+            "TEST",
+        );
+        // Your own troops are tracked anywhere, enemies only where you can see them arrive
+        assert_eq!(known.len(), 2);
+        assert!(known
+            .iter()
+            .all(|movement| movement.to_x != SIGHT_RANGE + 1));
+    }
+
+    #[test]
+    fn armies_get_one_arrow() {
+        let movement = |unit: Unit, amount: u32, moved_at: u64| Movement {
+            faction: "TEST".to_string(),
+            from_x: 0,
+            from_y: 0,
+            to_x: 2,
+            to_y: 1,
+            unit,
+            amount,
+            moved_at,
+        };
+        // The soldiers and knights marched together, the later soldiers are a different army
+        let combined = combine_movements(&[
+            movement(Unit::Soldier, 5, 100),
+            movement(Unit::Knight, 3, 100),
+            movement(Unit::Soldier, 4, 200),
+        ]);
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].amount, 8);
+        assert_eq!(combined[1].amount, 4);
+    }
+
+    #[test]
+    fn arrows_point_at_the_destination() {
+        let (shaft, head) = arrow_shape((0.0, 0.0), (150.0, 0.0));
+        assert_eq!(shaft.len(), 4);
+        assert_eq!(head.len(), 3);
+        assert_eq!((head[0].x, head[0].y), (150, 0));
+        assert!(shaft.iter().all(|point| point.x < 150));
+        // Too short to fit a head on
+        let (shaft, head) = arrow_shape((0.0, 0.0), (1.0, 1.0));
+        assert!(shaft.is_empty() && head.is_empty());
+    }
+
+    #[test]
+    fn counts_fit_next_to_icons() {
+        assert_eq!(short_count(950), "950");
+        assert_eq!(short_count(1_550), "1.5k");
+        assert_eq!(short_count(12_345), "12k");
+        assert_eq!(short_count(3_000_000), "3m");
+        // Every icon on the map has an embedded sprite
+        for name in [
+            "capital",
+            "farm",
+            "mill",
+            "blacksmith",
+            "barracks",
+            "house",
+            "hut",
+            "citizen",
+            "soldier",
+            "cavalry",
+            "ranger",
+            "knight",
+            "scout",
+        ] {
+            assert!(crate::MapIcons::get(&format!("{}.png", name)).is_some());
+        }
+    }
+
+    #[test]
+    fn factions_keep_their_colour() {
+        assert_eq!(tag_colour("ABCD"), tag_colour("abcd"));
+        assert_ne!(tag_colour("ABCD"), tag_colour("ABCE"));
+        let mut faction = Faction {
+            tag: "ABCD".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(faction.colour(), tag_colour("ABCD"));
+        faction.colour = Some([1, 2, 3]);
+        assert_eq!(faction.colour(), [1, 2, 3]);
+        assert_eq!(string_to_colour("#3A7BD5"), Ok([0x3A, 0x7B, 0xD5]));
+        assert_eq!(string_to_colour("3a7bd5"), Ok([0x3A, 0x7B, 0xD5]));
+        assert!(string_to_colour("#3A7BD").is_err());
+        assert!(string_to_colour("#GGGGGG").is_err());
+    }
+
+    #[test]
+    fn legend_wraps_onto_new_rows() {
+        let labels: Vec<(String, ())> = (0..10).map(|i| (format!("FAC{}", i), ())).collect();
+        let positions = legend_layout(&labels, 150, 1500);
+        assert_eq!(positions[0].1, 0);
+        assert!(positions.last().unwrap().1 > 0);
+        // Entries on the same row never overlap
+        for pair in positions.windows(2) {
+            assert!(pair[0].1 < pair[1].1 || pair[0].0 < pair[1].0);
+        }
+    }
+
+    #[test]
+    fn overview_covers_more_when_zoomed_out() {
+        let (x_range, y_range) = overview_range((0, 0), 10);
+        assert_eq!(x_range.1 - x_range.0 + 1, 80);
+        assert_eq!(y_range, x_range);
+        assert!(x_range.0 <= 0 && x_range.1 >= 0);
+        let (x_range, _) = overview_range((50, -20), 1);
+        assert_eq!(x_range.1 - x_range.0 + 1, 800);
+        assert_eq!((x_range.0 + x_range.1) / 2, 49);
+    }
+
+    #[test]
+    fn big_maps_are_scaled_down_to_fit() {
+        assert_eq!(map_tile_px(150, 9), 150);
+        assert_eq!(map_tile_px(60, 9), 60);
+        // Too small to read, and too big to draw
+        assert_eq!(map_tile_px(1, 9), 40);
+        assert_eq!(map_tile_px(500, 3), 150);
+        // A 21 tile map plus its gutter has to fit in the size limit
+        assert!(map_tile_px(150, 21) * 22 <= 2400);
+    }
+
+    #[test]
+    fn timelapses_spread_their_frames_out() {
+        let times: Vec<u64> = (0..100).map(|i| i * 1800).collect();
+        let frames = pick_frames(&times, 10);
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames[0], 0);
+        assert!(frames.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(*frames.last().unwrap() >= 90 * 1800);
+        // Short histories use every snapshot
+        assert_eq!(pick_frames(&times[..5], 10), times[..5].to_vec());
+    }
+
+    #[test]
+    fn snapshots_only_remember_the_owner() {
+        let snapshot = Snapshot {
+            x: 3,
+            y: -2,
+            faction: "ABC".to_string(),
+            taken_at: 100,
+        };
+        let tile = snapshot.to_tile();
+        assert_eq!((tile.x, tile.y), (3, -2));
+        assert_eq!(tile.faction, "ABC");
+        assert!(!is_abandoned(&tile));
+        assert!(tile.units.is_empty() && tile.buildings.is_empty());
+    }
+
+    #[test]
+    fn snapshots_only_record_changes() {
+        let previous = HashMap::from([
+            ((0, 0), "ABC".to_string()),
+            ((1, 0), "ABC".to_string()),
+            ((2, 0), "".to_string()),
+        ]);
+        let current = HashMap::from([((0, 0), "ABC".to_string()), ((1, 0), "XYZ".to_string())]);
+        let changes = ownership_changes(&previous, &current, 100);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].x, changes[0].faction.as_str()), (1, "XYZ"));
+        // Losing a tile is recorded as nobody owning it
+        let changes = ownership_changes(&current, &HashMap::new(), 200);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|change| change.faction.is_empty()));
+    }
+
+    #[test]
+    fn replays_build_on_earlier_changes() {
+        let change = |x: i32, faction: &str, taken_at: u64| Snapshot {
+            x,
+            y: 0,
+            faction: faction.to_string(),
+            taken_at,
+        };
+        let history = vec![
+            change(0, "ABC", 0),
+            change(1, "ABC", 100),
+            change(0, "XYZ", 200),
+            change(1, "", 300),
+        ];
+        let owners = replay(&history, &[50, 100, 250, 300]);
+        assert_eq!(owners[0].get(&(0, 0)).map(String::as_str), Some("ABC"));
+        assert_eq!(owners[0].get(&(1, 0)), None);
+        assert_eq!(owners[1].get(&(1, 0)).map(String::as_str), Some("ABC"));
+        assert_eq!(owners[2].get(&(0, 0)).map(String::as_str), Some("XYZ"));
+        assert_eq!(owners[3].get(&(1, 0)).map(String::as_str), Some(""));
+    }
+
+    #[test]
+    fn regions_split_negative_tiles_evenly() {
+        assert_eq!(region_of(0, 0), (0, 0));
+        assert_eq!(region_of(15, -1), (0, -1));
+        assert_eq!(region_of(16, -16), (1, -1));
+        assert_eq!(region_of(-17, 31), (-2, 1));
+    }
+
+    #[test]
+    fn cached_maps_go_stale_when_a_tile_changes() {
+        // Far away from anything else that might write tiles while the tests run
+        let x_range = (500_000, 500_010);
+        let y_range = (500_000, 500_010);
+        let key = MapKey {
+            x: 500_005,
+            y: 500_005,
+            faction: "ABC".to_string(),
+            history: None,
+            radius: 5,
+            tile_px: 150,
+        };
+        let revisions = render_cache::revisions(x_range, y_range);
+        render_cache::store_map(key.clone(), revisions.clone(), vec![1, 2, 3]);
+        assert_eq!(render_cache::get_map(&key, &revisions), Some(vec![1, 2, 3]));
+
+        // Changing a tile a long way off leaves it alone
+        render_cache::invalidate_tile(-500_000, -500_000);
+        let revisions = render_cache::revisions(x_range, y_range);
+        assert!(render_cache::get_map(&key, &revisions).is_some());
+
+        render_cache::invalidate_tile(500_003, 500_008);
+        let revisions = render_cache::revisions(x_range, y_range);
+        assert!(render_cache::get_map(&key, &revisions).is_none());
+    }
+
+    #[test]
+    fn backgrounds_are_only_drawn_once() {
+        let drawn = std::cell::Cell::new(0);
+        let draw = || {
+            drawn.set(drawn.get() + 1);
+            image::RgbImage::new(2, 2)
+        };
+        render_cache::background(600_000, 600_000, Shade::Fog, draw);
+        render_cache::background(600_000, 600_000, Shade::Fog, draw);
+        assert_eq!(drawn.get(), 1);
+        // Each shade is drawn separately
+        render_cache::background(600_000, 600_000, Shade::Visible, draw);
+        assert_eq!(drawn.get(), 2);
+    }
+
+    #[test]
+    fn maps_are_drawn_north_up() {
+        // A 2x2 map, with our tile in the north west and an enemy tile just south of it
+        let (x, y) = (900_000, 900_000);
+        let mut tiles = Vec::new();
+        for (tile_x, tile_y, faction) in [
+            (x, y + 1, "TEST"),
+            (x, y, "ENEM"),
+            (x + 1, y + 1, ""),
+            (x + 1, y, ""),
+        ] {
+            tiles.push(Tile {
+                x: tile_x,
+                y: tile_y,
+                occupied: !faction.is_empty(),
+                faction: faction.to_string(),
+                ..Default::default()
+            });
+        }
+        let colours = HashMap::from([("ENEM".to_string(), [200, 40, 40])]);
+        let image = render_map(tiles, "TEST", &Visibility::default(), &[], &colours, 150, 7);
+        // The coordinates take up the first 150 pixels, so the north west tile starts at 150, 150
+        assert_eq!(image.get_pixel(225, 225).0, [102, 178, 255]);
+        assert_eq!(image.get_pixel(225, 375).0, [200, 40, 40]);
+        // The border between the two runs along the bottom of our tile and the top of theirs
+        assert_eq!(image.get_pixel(225, 299).0, [20, 20, 20]);
+        assert_eq!(image.get_pixel(225, 300).0, [20, 20, 20]);
+        assert_ne!(image.get_pixel(225, 150).0, [20, 20, 20]);
+
+        // The top row of a tile's background is the northern edge, which carries on into the tile
+        // above it
+        let noise = Clamp::new(OpenSimplex::new(7)).set_bounds(0.1, 0.5);
+        let terrain = TerrainGenerator::new(7);
+        let background = draw_background(&noise, &terrain, x, y, Shade::Fog);
+        let value = noise.get([
+            (x * 150 + 20) as f64 / 100.0,
+            (y * 150 + 149) as f64 / 100.0,
+        ]);
+        let (r, g, b) = hsv_to_rgb(0.0, 0.0, value as f32 * 0.5);
+        assert_eq!(background.get_pixel(20, 0).0, [r, g, b]);
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
+use poise::serenity_prelude as serenity;
 use tokio::time::{sleep, Instant};
 
+use crate::config::get_config;
 use crate::db;
 
 pub async fn background_loop() {
@@ -10,7 +12,18 @@ pub async fn background_loop() {
     wait_times.insert("production", Instant::now());
     wait_times.insert("clean", Instant::now());
     wait_times.insert("economy", Instant::now());
+    wait_times.insert("training", Instant::now());
+    wait_times.insert("construction", Instant::now());
+    wait_times.insert("intel", Instant::now());
+    wait_times.insert("armies", Instant::now());
+    wait_times.insert("snapshots", Instant::now());
+    // Used to DM people when something they ordered is finished
+    let http = serenity::Http::new(&get_config().discord_token);
     sleep(Duration::from_millis(500)).await;
+    // Take a snapshot straight away, otherwise a bot that restarts often would never get far
+    // enough into the interval to record one
+    trace!("Recording tile ownership");
+    db::snapshots::record_ownership().await.unwrap();
     loop {
         if Instant::now()
             .duration_since(wait_times["production"])
@@ -25,9 +38,7 @@ pub async fn background_loop() {
         if Instant::now().duration_since(wait_times["clean"]).as_secs() >= 30 {
             wait_times.insert("clean", Instant::now());
             trace!("Cleaning database");
-            db::cleaners::clean_factions().await.unwrap();
-            db::cleaners::clean_tiles().await.unwrap();
-            db::cleaners::clean_users().await.unwrap();
+            db::cleaners::clean_all().await.unwrap();
         }
 
         if Instant::now()
@@ -39,5 +50,51 @@ pub async fn background_loop() {
             trace!("Updating economy");
             db::update_economy().await.unwrap();
         }
+
+        if Instant::now()
+            .duration_since(wait_times["training"])
+            .as_secs()
+            >= 5
+        {
+            wait_times.insert("training", Instant::now());
+            trace!("Finishing training orders");
+            db::training::complete_ready().await.unwrap();
+        }
+
+        if Instant::now()
+            .duration_since(wait_times["construction"])
+            .as_secs()
+            >= 5
+        {
+            wait_times.insert("construction", Instant::now());
+            trace!("Finishing construction jobs");
+            db::construction::complete_ready(&http).await.unwrap();
+        }
+
+        if Instant::now().duration_since(wait_times["intel"]).as_secs() >= 30 {
+            wait_times.insert("intel", Instant::now());
+            trace!("Updating intel");
+            db::intel::update_intel().await.unwrap();
+        }
+
+        if Instant::now()
+            .duration_since(wait_times["armies"])
+            .as_secs()
+            >= 5
+        {
+            wait_times.insert("armies", Instant::now());
+            trace!("Marching armies");
+            db::armies::march(&http).await.unwrap();
+        }
+
+        if Instant::now()
+            .duration_since(wait_times["snapshots"])
+            .as_secs()
+            >= db::snapshots::SNAPSHOT_INTERVAL
+        {
+            wait_times.insert("snapshots", Instant::now());
+            trace!("Recording tile ownership");
+            db::snapshots::record_ownership().await.unwrap();
+        }
     }
 }
\ No newline at end of file
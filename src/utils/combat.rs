@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use crate::types::units::Unit;
+
+// Defenders know the ground and have had time to dig in, so they get a small edge
+const DEFENDER_BONUS: f64 = 1.2;
+// How much stronger a unit is against a unit type it beats, and weaker against one that beats it
+const ADVANTAGE_BONUS: f64 = 1.0;
+const DISADVANTAGE_PENALTY: f64 = 0.5;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BattleResult {
+    pub(crate) attacker_won: bool,
+    pub(crate) attacker_power: f64,
+    pub(crate) defender_power: f64,
+    pub(crate) attacker_losses: HashMap<Unit, u32>,
+    pub(crate) defender_losses: HashMap<Unit, u32>,
+    pub(crate) attacker_survivors: HashMap<Unit, u32>,
+    pub(crate) defender_survivors: HashMap<Unit, u32>,
+}
+
+/// Works out how strong an army is when fighting a specific enemy army. Each unit starts with its
+/// base strength, then gets a bonus for the share of the enemy it beats and a penalty for the share
+/// of the enemy that beats it
+///
+/// # Arguments
+///
+/// * `army` - The units whose strength is being calculated
+/// * `enemy` - The units they are fighting against
+///
+/// # Returns
+/// ```f64```: The combat power of the army
+///
+pub(crate) fn army_power(army: &HashMap<Unit, u32>, enemy: &HashMap<Unit, u32>) -> f64 {
+    let enemy_total: u32 = enemy.values().sum();
+    let mut power = 0.0;
+    for (unit, amount) in army {
+        let data = unit.data();
+        let mut modifier = 1.0;
+        if enemy_total > 0 {
+            let mut beaten = 0;
+            let mut beaten_by = 0;
+            for (enemy_unit, enemy_amount) in enemy {
+                if data.beats.contains(enemy_unit) {
+                    beaten += enemy_amount;
+                }
+                if enemy_unit.data().beats.contains(unit) {
+                    beaten_by += enemy_amount;
+                }
+            }
+            modifier += ADVANTAGE_BONUS * beaten as f64 / enemy_total as f64;
+            modifier -= DISADVANTAGE_PENALTY * beaten_by as f64 / enemy_total as f64;
+        }
+        power += *amount as f64 * data.strength as f64 * modifier;
+    }
+    power
+}
+
+/// Resolves a battle between an attacking and a defending army. The losing side is wiped out, and
+/// the winning side loses a share of each unit type based on how close the fight was
+///
+/// # Arguments
+///
+/// * `attackers` - The units moving into the tile
+/// * `defenders` - The units already on the tile
+///
+/// # Returns
+/// ```BattleResult```: The outcome of the battle, including losses and survivors for both sides
+///
+pub(crate) fn resolve_battle(
+    attackers: &HashMap<Unit, u32>,
+    defenders: &HashMap<Unit, u32>,
+) -> BattleResult {
+    let attacker_power = army_power(attackers, defenders);
+    let defender_power = army_power(defenders, attackers) * DEFENDER_BONUS;
+    let undefended = defenders.values().sum::<u32>() == 0;
+    // Ties go to the defender, but an empty tile can't hold out against anyone
+    let attacker_won = undefended || attacker_power > defender_power;
+    let (winner, winner_power, loser_power) = if attacker_won {
+        (attackers, attacker_power, defender_power)
+    } else {
+        (defenders, defender_power, attacker_power)
+    };
+    let loss_ratio = if winner_power > 0.0 {
+        (loser_power / winner_power).min(1.0)
+    } else {
+        0.0
+    };
+    let mut winner_losses = HashMap::new();
+    let mut winner_survivors = HashMap::new();
+    for (unit, amount) in winner {
+        let lost = (*amount as f64 * loss_ratio).floor() as u32;
+        if lost > 0 {
+            winner_losses.insert(unit.clone(), lost);
+        }
+        if amount - lost > 0 {
+            winner_survivors.insert(unit.clone(), amount - lost);
+        }
+    }
+    let loser = if attacker_won { defenders } else { attackers };
+    let loser_losses: HashMap<Unit, u32> = loser
+        .iter()
+        .filter(|(_, amount)| **amount > 0)
+        .map(|(unit, amount)| (unit.clone(), *amount))
+        .collect();
+    if attacker_won {
+        BattleResult {
+            attacker_won,
+            attacker_power,
+            defender_power,
+            attacker_losses: winner_losses,
+            defender_losses: loser_losses,
+            attacker_survivors: winner_survivors,
+            defender_survivors: HashMap::new(),
+        }
+    } else {
+        BattleResult {
+            attacker_won,
+            attacker_power,
+            defender_power,
+            attacker_losses: loser_losses,
+            defender_losses: winner_losses,
+            attacker_survivors: HashMap::new(),
+            defender_survivors: winner_survivors,
+        }
+    }
+}
+
+/// Formats a set of units as a list for an embed field
+///
+/// # Arguments
+///
+/// * `units` - The units to list
+///
+/// # Returns
+/// ```String```: One line per unit type, or "None" if there are no units
+///
+pub(crate) fn format_units(units: &HashMap<Unit, u32>) -> String {
+    let mut formatted = "".to_string();
+    for (unit, amount) in units {
+        formatted.push_str(format!("{}: {}\n", unit.data().name, amount).as_str());
+    }
+    if formatted.is_empty() {
+        formatted = "None".to_string();
+    }
+    formatted
+}
@@ -4,6 +4,7 @@ use crate::commands::faction::FactionModal;
 use crate::types::buildings::Building;
 use crate::types::factions::Faction;
 use crate::types::map::Tile;
+use crate::types::permissions::Permissions;
 use crate::types::units::Unit;
 use crate::types::users::User;
 
@@ -82,6 +83,78 @@ pub(crate) async fn string_to_building(string: &String) -> Result<Building, bool
     }
 }
 
+/// Converts a string to a permission
+///
+/// # Arguments
+///
+/// * `string`: The name of the permission to get
+///
+/// returns: Result<Permissions, bool>
+pub(crate) async fn string_to_permission(string: &str) -> Result<Permissions, bool> {
+    for permission in Permissions::all() {
+        if permission.to_string().to_lowercase() == string.to_lowercase() {
+            return Ok(permission);
+        }
+    }
+    Err(false)
+}
+
+/// Converts a hex colour like "#3A7BD5" to RGB. The # is optional
+///
+/// # Arguments
+///
+/// * `string`: The colour to convert
+///
+/// returns: Result<[u8; 3], bool>
+pub(crate) fn string_to_colour(string: &str) -> Result<[u8; 3], bool> {
+    let hex = string.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(false);
+    }
+    let mut colour = [0; 3];
+    for (index, channel) in colour.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| false)?;
+    }
+    Ok(colour)
+}
+
 pub fn day_to_seconds(day: f32) -> f32 {
     day / 60.0 / 60.0 / 24.0
+}
+
+/// Converts an HSV color to RGB
+///
+/// # Arguments
+///
+/// * `h` The hue value, from 0 to 360
+/// * `s` The saturation value, from 0 to 100
+/// * `v` The value value, from 0 to 1
+///
+/// # Returns
+///
+/// * `(u8, u8, u8)` A tuple containing the RGB values
+pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
+    let c = v * (s / 100.0);
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+
+    let (r, g, b) = if h < 60.0 {
+        (c, x, 0.0)
+    } else if h < 120.0 {
+        (x, c, 0.0)
+    } else if h < 180.0 {
+        (0.0, c, x)
+    } else if h < 240.0 {
+        (0.0, x, c)
+    } else if h < 300.0 {
+        (x, 0.0, c)
+    } else {
+        (c, 0.0, x)
+    };
+
+    let r = (r + m) * 255.0;
+    let g = (g + m) * 255.0;
+    let b = (b + m) * 255.0;
+
+    (r as u8, g as u8, b as u8)
 }
\ No newline at end of file
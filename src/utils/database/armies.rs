@@ -0,0 +1,326 @@
+use std::time::SystemTime;
+
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::FindOptions;
+use mongodb::Database;
+use poise::serenity_prelude as serenity;
+
+use crate::combat::{format_units, resolve_battle};
+use crate::config::get_config;
+use crate::db;
+use crate::misc::send_dm;
+use crate::terrain::TerrainGenerator;
+use crate::types::diplomacy::Stance;
+use crate::types::units::Army;
+
+pub(crate) async fn add_army(army: Army) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    db.collection::<Army>("armies")
+        .insert_one(army, None)
+        .await?;
+    Ok(())
+}
+
+/// Gets every army a faction has on the march, with the ones arriving soonest first
+pub(crate) async fn get_all_by_faction(
+    faction: String,
+) -> Result<Vec<Army>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let options = FindOptions::builder().sort(doc! {"arrives_at": 1}).build();
+    let cursor = db
+        .collection::<Army>("armies")
+        .find(doc! {"faction": faction}, options)
+        .await?;
+    let all: Vec<Army> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn get_all() -> Result<Vec<Army>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    internal_get_all(&db).await
+}
+
+pub(crate) async fn internal_get_all(db: &Database) -> Result<Vec<Army>, mongodb::error::Error> {
+    let cursor = db.collection::<Army>("armies").find(None, None).await?;
+    let all: Vec<Army> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn internal_get_army(
+    db: &Database,
+    id: String,
+) -> Result<Option<Army>, mongodb::error::Error> {
+    let options = FindOptions::builder().limit(1).build();
+    let mut cursor = db
+        .collection::<Army>("armies")
+        .find(doc! {"id": id}, options)
+        .await?;
+    cursor.try_next().await
+}
+
+pub(crate) async fn internal_get_due(
+    db: &Database,
+    now: u64,
+) -> Result<Vec<Army>, mongodb::error::Error> {
+    let filter = doc! {"next_step_at": {"$lte": now as i64}};
+    let cursor = db.collection::<Army>("armies").find(filter, None).await?;
+    let all: Vec<Army> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn internal_save_army(
+    db: &Database,
+    army: Army,
+) -> Result<(), mongodb::error::Error> {
+    db.collection::<Army>("armies")
+        .replace_one(doc! {"id": army.id.clone()}, army, None)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn internal_delete_army(
+    db: &Database,
+    id: String,
+) -> Result<(), mongodb::error::Error> {
+    db.collection::<Army>("armies")
+        .delete_one(doc! {"id": id}, None)
+        .await?;
+    Ok(())
+}
+
+// Sends an army back the way it came, and works out when it will get there
+fn send_home(army: &mut Army, terrain: &TerrainGenerator, now: u64) {
+    army.turn_back();
+    army.next_step_at = now + army.step_time(terrain);
+    army.arrives_at = now + army.remaining_time(terrain);
+}
+
+/// Moves every army that is due along its route, one tile at a time. Armies at war with each other
+/// fight when their paths cross, and armies that reach the end of their route either settle on
+/// the tile or attack whoever holds it. Whoever sent the army is told how it went
+pub async fn march(http: &serenity::Http) -> Result<(), mongodb::error::Error> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let db = db::get_db().await?;
+    let terrain = TerrainGenerator::new(get_config().perlin_seed);
+    for due in internal_get_due(&db, now).await? {
+        // It might have run into an army that marched earlier in this round
+        let mut army = match internal_get_army(&db, due.id).await? {
+            Some(army) => army,
+            None => continue,
+        };
+        let mut reports = Vec::new();
+        let mut destroyed = false;
+        while !army.arrived() && army.next_step_at <= now {
+            let next = army.route[army.step as usize + 1];
+            // Somebody might have taken a tile on the way since the army set off
+            if next != army.destination() {
+                let tile = db::tiles::internal_get_tile(&db, next.0, next.1).await?;
+                let blocked = !tile.faction.is_empty()
+                    && tile.faction != army.faction
+                    && db::relations::get_stance(army.faction.clone(), tile.faction.clone())
+                        .await?
+                        != Stance::Alliance;
+                if blocked && army.returning {
+                    reports.push(format!(
+                        "Your army was cut off by {} at {}, {} on the way home and scattered.",
+                        tile.faction, next.0, next.1
+                    ));
+                    destroyed = true;
+                    break;
+                }
+                if blocked {
+                    reports.push(format!(
+                        "Your army found the way blocked by {} at {}, {} and is heading back.",
+                        tile.faction, next.0, next.1
+                    ));
+                    send_home(&mut army, &terrain, now);
+                    continue;
+                }
+            }
+            army.step += 1;
+            army.next_step_at += army.step_time(&terrain);
+
+            // Armies at war fight wherever they run into each other
+            let (x, y) = army.position();
+            for mut other in internal_get_all(&db).await? {
+                if other.id == army.id || other.faction == army.faction || !army.meets(&other) {
+                    continue;
+                }
+                let stance =
+                    db::relations::get_stance(army.faction.clone(), other.faction.clone()).await?;
+                if stance != Stance::War {
+                    continue;
+                }
+                let result = resolve_battle(&army.units, &other.units);
+                let losses = format!(
+                    "Your losses:\n{}\nEnemy losses:\n{}",
+                    format_units(&result.attacker_losses),
+                    format_units(&result.defender_losses)
+                );
+                let other_losses = format!(
+                    "Your losses:\n{}\nEnemy losses:\n{}",
+                    format_units(&result.defender_losses),
+                    format_units(&result.attacker_losses)
+                );
+                if result.attacker_won {
+                    reports.push(format!(
+                        "Your army intercepted an army from {} near {}, {} and wiped it out!\n{}",
+                        other.faction, x, y, losses
+                    ));
+                    send_dm(
+                        http,
+                        &other.ordered_by,
+                        format!(
+                            "Your army was intercepted by {} near {}, {} and wiped out!\n{}",
+                            army.faction, x, y, other_losses
+                        ),
+                    )
+                    .await;
+                    army.units = result.attacker_survivors;
+                    internal_delete_army(&db, other.id).await?;
+                } else {
+                    reports.push(format!(
+                        "Your army ran into an army from {} near {}, {} and was wiped out!\n{}",
+                        other.faction, x, y, losses
+                    ));
+                    send_dm(
+                        http,
+                        &other.ordered_by,
+                        format!(
+                            "Your army held off an attack from {} near {}, {}!\n{}",
+                            army.faction, x, y, other_losses
+                        ),
+                    )
+                    .await;
+                    other.units = result.defender_survivors;
+                    internal_save_army(&db, other).await?;
+                    destroyed = true;
+                    break;
+                }
+            }
+            if destroyed || army.units.values().sum::<u32>() == 0 {
+                destroyed = true;
+                break;
+            }
+        }
+
+        if destroyed {
+            internal_delete_army(&db, army.id.clone()).await?;
+        } else if army.arrived() {
+            reports.push(arrive(&db, army.clone(), &terrain, now).await?);
+        } else {
+            internal_save_army(&db, army.clone()).await?;
+        }
+        if !reports.is_empty() {
+            send_dm(http, &army.ordered_by, reports.join("\n\n")).await;
+        }
+    }
+    Ok(())
+}
+
+/// Deals with an army reaching the end of its route. If the tile is empty or already theirs the
+/// army settles there, otherwise it attacks whoever holds it
+///
+/// # Arguments
+///
+/// * `db` - A database connection
+/// * `army` - The army that has arrived
+/// * `terrain` - The generator for the map's terrain
+/// * `now` - The current epoch time
+///
+/// # Returns
+/// ```String```: What happened, to tell whoever sent the army
+///
+async fn arrive(
+    db: &Database,
+    mut army: Army,
+    terrain: &TerrainGenerator,
+    now: u64,
+) -> Result<String, mongodb::error::Error> {
+    let (x, y) = army.destination();
+    let mut tile = db::tiles::internal_get_tile(db, x, y).await?;
+    if tile.faction.is_empty() || tile.faction == army.faction {
+        for (unit, amount) in army.units.clone() {
+            let new_amount = tile.units.get(&unit).unwrap_or(&0) + amount;
+            tile.units.insert(unit, new_amount);
+        }
+        tile.faction = army.faction.clone();
+        tile.occupied = true;
+        db::tiles::internal_set_tile(db, tile).await?;
+        db::movements::internal_record_army(db, &army, now).await?;
+        internal_delete_army(db, army.id.clone()).await?;
+        return Ok(if army.returning {
+            format!("Your army made it back to {}, {}.", x, y)
+        } else {
+            format!(
+                "Your army arrived at {}, {}:\n{}",
+                x,
+                y,
+                format_units(&army.units)
+            )
+        });
+    }
+
+    // Diplomacy might have changed while the army was on the march
+    let stance = db::relations::get_stance(army.faction.clone(), tile.faction.clone()).await?;
+    if stance.is_peaceful() {
+        if army.returning {
+            internal_delete_army(db, army.id.clone()).await?;
+            return Ok(format!(
+                "Your army came home to find {} holding {}, {} and scattered.",
+                tile.faction, x, y
+            ));
+        }
+        let message = format!(
+            "Your army reached {}, {}, but you have {} with {} now. It is heading back.",
+            x,
+            y,
+            stance.description(),
+            tile.faction
+        );
+        send_home(&mut army, terrain, now);
+        internal_save_army(db, army).await?;
+        return Ok(message);
+    }
+
+    // Attacking a faction you had no dealings with is as good as declaring war
+    if stance == Stance::Neutral {
+        db::relations::declare_war(army.faction.clone(), tile.faction.clone()).await?;
+    }
+    let defender = tile.faction.clone();
+    let result = resolve_battle(&army.units, &tile.units);
+    if result.attacker_won {
+        tile.units = result.attacker_survivors.clone();
+        tile.faction = army.faction.clone();
+        tile.occupied = true;
+        army.units = result.attacker_survivors.clone();
+        db::movements::internal_record_army(db, &army, now).await?;
+    } else {
+        tile.units = result.defender_survivors.clone();
+    }
+    db::tiles::internal_set_tile(db, tile).await?;
+    internal_delete_army(db, army.id.clone()).await?;
+    let outcome = if result.attacker_won {
+        format!(
+            "Victory at {}, {}! Your forces overwhelmed the defenders and took the tile.",
+            x, y
+        )
+    } else {
+        format!(
+            "Defeat at {}, {}! Your forces were wiped out and {} still holds the tile.",
+            x, y, defender
+        )
+    };
+    Ok(format!(
+        "{}\nAttacking strength: {:.1}\nDefending strength: {:.1}\nYour losses:\n{}\nEnemy losses:\n{}",
+        outcome,
+        result.attacker_power,
+        result.defender_power,
+        format_units(&result.attacker_losses),
+        format_units(&result.defender_losses)
+    ))
+}
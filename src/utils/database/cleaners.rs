@@ -1,10 +1,31 @@
+use std::time::SystemTime;
+
+use crate::types::diplomacy::Stance;
 use crate::types::factions::Faction;
 use crate::types::map::Tile;
 use crate::types::users::User;
 use crate::{db, Error};
 
+/// Runs every cleaner, so the background loop and the dev command always clean the same things
+pub async fn clean_all() -> Result<(), Error> {
+    clean_factions().await?;
+    clean_tiles().await?;
+    clean_users().await?;
+    clean_relations().await?;
+    clean_intel().await?;
+    clean_armies().await?;
+    clean_movements().await?;
+    clean_snapshots().await?;
+    Ok(())
+}
+
 pub async fn clean_users() -> Result<(), Error> {
     let users = db::users::get_all().await?;
+    let tags: Vec<String> = db::factions::get_all()
+        .await?
+        .into_iter()
+        .map(|faction| faction.tag)
+        .collect();
     let mut cleaned_users: Vec<User> = Vec::new();
     for user in users {
         let mut cleaned_user = user.clone();
@@ -13,6 +34,11 @@ pub async fn clean_users() -> Result<(), Error> {
             .permissions
             .sort_by(|a, b| a.to_string().cmp(&b.to_string()));
         cleaned_user.faction = cleaned_user.faction.to_uppercase();
+        if !cleaned_user.faction.is_empty() && !tags.contains(&cleaned_user.faction) {
+            // Their faction is gone, so they are free agents again
+            cleaned_user.faction = "".to_string();
+            cleaned_user.permissions.clear();
+        }
         cleaned_users.push(cleaned_user);
     }
     db::users::set_many(cleaned_users).await?;
@@ -27,17 +53,7 @@ pub async fn clean_tiles() -> Result<(), Error> {
     for tile in tiles {
         let mut cleaned_tile = tile.clone();
         cleaned_tile.faction = cleaned_tile.faction.to_uppercase();
-        let mut to_delete_tile = false;
-        if cleaned_tile.faction == "" {
-            to_delete_tile = true;
-        }
-        if cleaned_tile.buildings.len() == 0 && cleaned_tile.units.len() == 0 {
-            to_delete_tile = true;
-        }
-        if !cleaned_tile.occupied {
-            to_delete_tile = true;
-        }
-        if to_delete_tile {
+        if is_abandoned(&cleaned_tile) {
             to_delete.push(cleaned_tile.clone());
         } else {
             cleaned_tiles.push(cleaned_tile.clone());
@@ -51,6 +67,15 @@ pub async fn clean_tiles() -> Result<(), Error> {
     Ok(())
 }
 
+/// Whether a tile has been left with nothing worth keeping. Claimed tiles stay owned even when
+/// they are empty
+pub(crate) fn is_abandoned(tile: &Tile) -> bool {
+    if tile.faction.is_empty() || !tile.occupied {
+        return true;
+    }
+    tile.buildings.is_empty() && tile.units.is_empty() && !tile.claimed
+}
+
 pub async fn clean_factions() -> Result<(), Error> {
     let factions = db::factions::get_all().await?;
     let mut cleaned_factions: Vec<Faction> = Vec::new();
@@ -77,9 +102,68 @@ pub async fn clean_factions() -> Result<(), Error> {
         }
     }
     db::factions::set_many(cleaned_factions).await?;
-    let conn = db::get_db().await?;
     for faction in to_delete {
-        db::factions::internal_delete_faction(&conn, faction.tag).await?;
+        // Disband rather than just deleting so no users or tiles are left pointing at the faction
+        db::factions::disband_faction(faction.tag).await?;
+    }
+    Ok(())
+}
+
+pub async fn clean_relations() -> Result<(), Error> {
+    let relations = db::relations::get_all().await?;
+    let conn = db::get_db().await?;
+    for relation in relations {
+        let orphaned = !db::factions::internal_faction_exists(&conn, relation.faction_a.clone())
+            .await?
+            || !db::factions::internal_faction_exists(&conn, relation.faction_b.clone()).await?;
+        let forgotten = relation.stance == Stance::Neutral && relation.proposal.is_none();
+        if orphaned || forgotten {
+            db::relations::internal_delete_relation(&conn, relation).await?;
+        }
     }
     Ok(())
+}
+
+pub async fn clean_intel() -> Result<(), Error> {
+    let conn = db::get_db().await?;
+    for intel in db::intel::get_all().await? {
+        // Nobody is left to remember it
+        if !db::factions::internal_faction_exists(&conn, intel.faction.clone()).await? {
+            db::intel::internal_delete_intel(&conn, &intel).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn clean_armies() -> Result<(), Error> {
+    let conn = db::get_db().await?;
+    for army in db::armies::get_all().await? {
+        // Nobody is left to march for
+        if !db::factions::internal_faction_exists(&conn, army.faction.clone()).await? {
+            db::armies::internal_delete_army(&conn, army.id).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn clean_movements() -> Result<(), Error> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    // Too old to show up on anyone's map
+    let cutoff = now.saturating_sub(db::movements::HISTORY_HOURS * 60 * 60);
+    db::movements::delete_before(cutoff).await?;
+    Ok(())
+}
+
+pub async fn clean_snapshots() -> Result<(), Error> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    // Too old to be played back in a timelapse, so only who owned what by then is needed
+    let cutoff = now.saturating_sub(db::snapshots::SNAPSHOT_HOURS * 60 * 60);
+    db::snapshots::compact_before(cutoff).await?;
+    Ok(())
 }
\ No newline at end of file
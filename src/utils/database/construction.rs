@@ -0,0 +1,158 @@
+use std::time::SystemTime;
+
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::FindOptions;
+use mongodb::Database;
+use poise::serenity_prelude as serenity;
+
+use crate::db;
+use crate::misc::send_dm;
+use crate::types::buildings::ConstructionJob;
+
+pub(crate) async fn add_job(job: ConstructionJob) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    db.collection::<ConstructionJob>("construction")
+        .insert_one(job, None)
+        .await?;
+    Ok(())
+}
+
+/// Gets every job a faction has queued, with the ones finishing soonest first
+pub(crate) async fn get_all_by_faction(
+    faction: String,
+) -> Result<Vec<ConstructionJob>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let options = FindOptions::builder().sort(doc! {"ready_at": 1}).build();
+    let cursor = db
+        .collection::<ConstructionJob>("construction")
+        .find(doc! {"faction": faction}, options)
+        .await?;
+    let all: Vec<ConstructionJob> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+/// Gets every job queued on a tile, in the order they will be worked on
+pub(crate) async fn get_all_by_tile(
+    x: i32,
+    y: i32,
+) -> Result<Vec<ConstructionJob>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let options = FindOptions::builder().sort(doc! {"ready_at": 1}).build();
+    let cursor = db
+        .collection::<ConstructionJob>("construction")
+        .find(doc! {"x": x, "y": y}, options)
+        .await?;
+    let all: Vec<ConstructionJob> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn save_job(job: ConstructionJob) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    db.collection::<ConstructionJob>("construction")
+        .replace_one(doc! {"id": job.id.clone()}, job, None)
+        .await?;
+    Ok(())
+}
+
+/// Gets a single job by its ID, or None if it has already finished or been cancelled
+pub(crate) async fn get_job(id: String) -> Result<Option<ConstructionJob>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    db.collection::<ConstructionJob>("construction")
+        .find_one(doc! {"id": id}, None)
+        .await
+}
+
+/// Deletes a job. Returns whether there was a job to delete, so whoever gets there first between
+/// cancelling and finishing is the only one that acts on it
+pub(crate) async fn delete_job(id: String) -> Result<bool, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    internal_delete_job(&db, id).await
+}
+
+pub(crate) async fn internal_get_ready(
+    db: &Database,
+    now: u64,
+) -> Result<Vec<ConstructionJob>, mongodb::error::Error> {
+    let filter = doc! {"ready_at": {"$lte": now as i64}};
+    let cursor = db
+        .collection::<ConstructionJob>("construction")
+        .find(filter, None)
+        .await?;
+    let all: Vec<ConstructionJob> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn internal_delete_job(
+    db: &Database,
+    id: String,
+) -> Result<bool, mongodb::error::Error> {
+    let result = db
+        .collection::<ConstructionJob>("construction")
+        .delete_one(doc! {"id": id}, None)
+        .await?;
+    Ok(result.deleted_count > 0)
+}
+
+/// Works out when the jobs on a tile will start and finish. Work that has already started carries
+/// on as it was, and everything after it starts as soon as the job in front of it is done
+///
+/// # Arguments
+///
+/// * `jobs` - The jobs on a single tile, in the order they should be worked on
+/// * `now` - The current epoch time
+///
+/// # Returns
+/// ```Vec<ConstructionJob>```: The jobs with their new start and finish times
+///
+pub(crate) fn reschedule(jobs: Vec<ConstructionJob>, now: u64) -> Vec<ConstructionJob> {
+    let mut free_at = now;
+    let mut scheduled = Vec::new();
+    for mut job in jobs {
+        if job.started_at > now {
+            let duration = job.ready_at - job.started_at;
+            job.started_at = free_at.max(now);
+            job.ready_at = job.started_at + duration;
+        }
+        free_at = free_at.max(job.ready_at);
+        scheduled.push(job);
+    }
+    scheduled
+}
+
+/// Puts every finished job's buildings on its tile and lets whoever ordered it know. If the
+/// faction has lost the tile in the meantime, the half-finished buildings are abandoned
+pub async fn complete_ready(http: &serenity::Http) -> Result<(), mongodb::error::Error> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let db = db::get_db().await?;
+    for job in internal_get_ready(&db, now).await? {
+        // The job was cancelled while we were looking at it
+        if !internal_delete_job(&db, job.id.clone()).await? {
+            continue;
+        }
+        let mut tile = db::tiles::internal_get_tile(&db, job.x, job.y).await?;
+        let name = job.building.data().name;
+        let message = if tile.faction == job.faction {
+            tile.add_buildings(job.building.clone(), 1, job.amount);
+            db::tiles::internal_set_tile(&db, tile).await?;
+            format!(
+                "Construction of {} {} at {}, {} is finished!",
+                job.amount, name, job.x, job.y
+            )
+        } else {
+            warn!(
+                "{} lost {}, {} before their {} {} finished construction",
+                job.faction, job.x, job.y, job.amount, name
+            );
+            format!(
+                "Your faction lost {}, {} before construction of {} {} finished, so the work was abandoned.",
+                job.x, job.y, job.amount, name
+            )
+        };
+        send_dm(http, &job.ordered_by, message).await;
+    }
+    Ok(())
+}
@@ -98,4 +98,29 @@ pub(crate) async fn internal_delete_faction(
 pub(crate) async fn delete_faction(tag: String) -> Result<(), mongodb::error::Error> {
     let db = db::get_db().await?;
     internal_delete_faction(&db, tag).await
+}
+
+/// Disbands a faction. Deletes the faction, releases all of its tiles and removes every member
+/// from it. Creates its own database connection
+///
+/// # Arguments
+///
+/// * `tag` - The tag of the faction to disband
+///
+/// # Returns
+/// ```Result<(), mongodb::error::Error>```: The result of the operation
+///
+pub(crate) async fn disband_faction(tag: String) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    for tile in db::tiles::get_all_by_faction(tag.clone()).await? {
+        db::tiles::internal_delete_tile(&db, tile.x, tile.y).await?;
+    }
+    for mut user in db::users::get_all().await? {
+        if user.faction == tag {
+            user.faction = "".to_string();
+            user.permissions.clear();
+            db::users::internal_save_user(&db, user).await?;
+        }
+    }
+    internal_delete_faction(&db, tag).await
 }
\ No newline at end of file
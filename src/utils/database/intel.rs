@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
+
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::{FindOptions, ReplaceOptions};
+use mongodb::Database;
+
+use crate::db;
+use crate::types::map::Intel;
+use crate::vision::Visibility;
+
+/// Gets what a faction last saw of a tile. Creates its own database connection
+///
+/// # Arguments
+///
+/// * `faction` - The tag of the faction
+/// * `x` - The x coordinate of the tile
+/// * `y` - The y coordinate of the tile
+///
+/// # Returns
+/// ```Option<Intel>```: The faction's last sighting of the tile, if it has ever seen it
+///
+pub(crate) async fn get_intel(
+    faction: String,
+    x: i32,
+    y: i32,
+) -> Result<Option<Intel>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let filter = doc! {"faction": faction, "x": x, "y": y};
+    let options = FindOptions::builder().limit(1).build();
+    let mut cursor = db
+        .collection::<Intel>("intel")
+        .find(filter, options)
+        .await?;
+    cursor.try_next().await
+}
+
+/// Gets everything a faction remembers about the tiles in an area
+pub(crate) async fn get_many(
+    faction: String,
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+) -> Result<Vec<Intel>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let filter = doc! {
+        "faction": faction,
+        "x": {"$gte": x_range.0, "$lte": x_range.1},
+        "y": {"$gte": y_range.0, "$lte": y_range.1},
+    };
+    let cursor = db.collection::<Intel>("intel").find(filter, None).await?;
+    let all: Vec<Intel> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn get_all() -> Result<Vec<Intel>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let cursor = db.collection::<Intel>("intel").find(None, None).await?;
+    let all: Vec<Intel> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn internal_save_intel(
+    db: &Database,
+    intel: Intel,
+) -> Result<(), mongodb::error::Error> {
+    let filter = doc! {"faction": intel.faction.clone(), "x": intel.x, "y": intel.y};
+    let options = ReplaceOptions::builder().upsert(true).build();
+    db.collection::<Intel>("intel")
+        .replace_one(filter, intel, options)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn internal_delete_intel(
+    db: &Database,
+    intel: &Intel,
+) -> Result<(), mongodb::error::Error> {
+    let filter = doc! {"faction": intel.faction.clone(), "x": intel.x, "y": intel.y};
+    db.collection::<Intel>("intel")
+        .delete_one(filter, None)
+        .await?;
+    Ok(())
+}
+
+/// Updates what every faction remembers about the tiles around it. Enemy tiles that are in sight
+/// are written down as they are now, and anything remembered about a tile that is in sight but no
+/// longer held is forgotten. Sightings that haven't changed only have their time bumped, all in
+/// one go, so a quiet border doesn't cost a write per tile
+pub async fn update_intel() -> Result<(), mongodb::error::Error> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let db = db::get_db().await?;
+    let collection = db.collection::<Intel>("intel");
+    for faction in db::factions::get_all().await? {
+        let territory = db::tiles::get_all_by_faction(faction.tag.clone()).await?;
+        let visibility = Visibility::from_territory(&territory);
+        if visibility.seen.is_empty() {
+            continue;
+        }
+        // Only the area the faction can see is read, so the work grows with its borders rather
+        // than with the whole map
+        let xs = visibility.seen.iter().map(|(x, _)| *x);
+        let ys = visibility.seen.iter().map(|(_, y)| *y);
+        let x_range = (xs.clone().min().unwrap(), xs.max().unwrap());
+        let y_range = (ys.clone().min().unwrap(), ys.max().unwrap());
+        let tiles = db::tiles::get_many(x_range, y_range).await?;
+        let known: HashMap<(i32, i32), Intel> = get_many(faction.tag.clone(), x_range, y_range)
+            .await?
+            .into_iter()
+            .map(|intel| ((intel.x, intel.y), intel))
+            .collect();
+        let mut observed = HashSet::new();
+        let mut unchanged = Vec::new();
+        for tile in &tiles {
+            if tile.faction.is_empty()
+                || tile.faction == faction.tag
+                || !visibility.can_see(tile.x, tile.y)
+            {
+                continue;
+            }
+            observed.insert((tile.x, tile.y));
+            let masked = visibility.mask(tile.clone(), &faction.tag);
+            match known.get(&(tile.x, tile.y)) {
+                Some(intel) if intel.tile == masked => {
+                    unchanged.push(doc! {"x": tile.x, "y": tile.y});
+                }
+                _ => {
+                    let intel = Intel {
+                        faction: faction.tag.clone(),
+                        x: tile.x,
+                        y: tile.y,
+                        tile: masked,
+                        seen_at: now,
+                    };
+                    internal_save_intel(&db, intel).await?;
+                }
+            }
+        }
+        if !unchanged.is_empty() {
+            collection
+                .update_many(
+                    doc! {"faction": faction.tag.clone(), "$or": unchanged},
+                    doc! {"$set": {"seen_at": now as i64}},
+                    None,
+                )
+                .await?;
+        }
+        let forgotten: Vec<Document> = known
+            .keys()
+            .filter(|(x, y)| visibility.can_see(*x, *y) && !observed.contains(&(*x, *y)))
+            .map(|(x, y)| doc! {"x": x, "y": y})
+            .collect();
+        if !forgotten.is_empty() {
+            collection
+                .delete_many(
+                    doc! {"faction": faction.tag.clone(), "$or": forgotten},
+                    None,
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::Database;
+
+use crate::db;
+use crate::types::units::{Army, Movement};
+
+// How many hours of troop movements are kept around to be shown on the map
+pub(crate) const HISTORY_HOURS: u64 = 72;
+
+/// Records where an army went once it has got there, one movement for each type of unit in it.
+/// Uses a pre-existing database connection
+///
+/// # Arguments
+///
+/// * `db` - A database connection
+/// * `army` - The army that arrived
+/// * `now` - The current epoch time
+///
+pub(crate) async fn internal_record_army(
+    db: &Database,
+    army: &Army,
+    now: u64,
+) -> Result<(), mongodb::error::Error> {
+    let (from_x, from_y) = army.route[0];
+    let (to_x, to_y) = army.destination();
+    let movements: Vec<Movement> = army
+        .units
+        .iter()
+        .filter(|(_, amount)| **amount > 0)
+        .map(|(unit, amount)| Movement {
+            faction: army.faction.clone(),
+            from_x,
+            from_y,
+            to_x,
+            to_y,
+            unit: unit.clone(),
+            amount: *amount,
+            moved_at: now,
+        })
+        .collect();
+    if movements.is_empty() {
+        return Ok(());
+    }
+    db.collection::<Movement>("movements")
+        .insert_many(movements, None)
+        .await?;
+    Ok(())
+}
+
+/// Gets every movement since a given time that started or ended in an area. Creates its own
+/// database connection
+///
+/// # Arguments
+///
+/// * `x_range` - The minimum and maximum x values of the area
+/// * `y_range` - The minimum and maximum y values of the area
+/// * `since` - The earliest epoch time to include
+///
+/// # Returns
+/// ```Vec<Movement>```: The movements, oldest first
+///
+pub(crate) async fn get_recent(
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+    since: u64,
+) -> Result<Vec<Movement>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let filter = doc! {
+        "moved_at": {"$gte": since as i64},
+        "$or": [
+            {"from_x": {"$gte": x_range.0, "$lte": x_range.1}, "from_y": {"$gte": y_range.0, "$lte": y_range.1}},
+            {"to_x": {"$gte": x_range.0, "$lte": x_range.1}, "to_y": {"$gte": y_range.0, "$lte": y_range.1}},
+        ],
+    };
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! {"moved_at": 1})
+        .build();
+    let cursor = db
+        .collection::<Movement>("movements")
+        .find(filter, options)
+        .await?;
+    let all: Vec<Movement> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+/// Forgets every movement from before a given time. Creates its own database connection
+pub(crate) async fn delete_before(before: u64) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    db.collection::<Movement>("movements")
+        .delete_many(doc! {"moved_at": {"$lt": before as i64}}, None)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use std::time::SystemTime;
+
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::options::FindOptions;
+use mongodb::Database;
+
+use crate::db;
+use crate::types::diplomacy::{Relation, Stance};
+
+/// Gets the relation between two factions. Creates its own database connection
+///
+/// # Arguments
+///
+/// * `first` - The tag of one of the factions
+/// * `second` - The tag of the other faction
+///
+/// # Returns
+/// ```Relation```: The relation between the factions. If they have never interacted, this is a
+/// neutral relation that hasn't been saved yet
+///
+pub(crate) async fn get_relation(
+    first: String,
+    second: String,
+) -> Result<Relation, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    internal_get_relation(&db, first, second).await
+}
+
+pub(crate) async fn internal_get_relation(
+    db: &Database,
+    first: String,
+    second: String,
+) -> Result<Relation, mongodb::error::Error> {
+    let relation = Relation::new(&first, &second);
+    let collection = db.collection::<Relation>("relations");
+    let filter =
+        doc! {"faction_a": relation.faction_a.clone(), "faction_b": relation.faction_b.clone()};
+    let options = FindOptions::builder().limit(1).build();
+    let mut cursor = collection.find(filter, options).await?;
+    match cursor.try_next().await? {
+        Some(existing) => Ok(existing),
+        None => Ok(relation),
+    }
+}
+
+/// Gets the stance between two factions. A faction is always neutral towards itself
+///
+/// # Arguments
+///
+/// * `first` - The tag of one of the factions
+/// * `second` - The tag of the other faction
+///
+/// # Returns
+/// ```Stance```: The current stance between the factions
+///
+pub(crate) async fn get_stance(
+    first: String,
+    second: String,
+) -> Result<Stance, mongodb::error::Error> {
+    if first == second || first.is_empty() || second.is_empty() {
+        return Ok(Stance::Neutral);
+    }
+    Ok(get_relation(first, second).await?.stance)
+}
+
+pub(crate) async fn save_relation(relation: Relation) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    internal_save_relation(&db, relation).await
+}
+
+pub(crate) async fn internal_save_relation(
+    db: &Database,
+    relation: Relation,
+) -> Result<(), mongodb::error::Error> {
+    let collection = db.collection::<Relation>("relations");
+    let filter =
+        doc! {"faction_a": relation.faction_a.clone(), "faction_b": relation.faction_b.clone()};
+    let options = mongodb::options::ReplaceOptions::builder()
+        .upsert(true)
+        .build();
+    collection.replace_one(filter, relation, options).await?;
+    Ok(())
+}
+
+/// Puts two factions at war, throwing out anything either of them had proposed. Creates its own
+/// database connection
+///
+/// # Arguments
+///
+/// * `first` - The tag of one of the factions
+/// * `second` - The tag of the other faction
+///
+pub(crate) async fn declare_war(
+    first: String,
+    second: String,
+) -> Result<(), mongodb::error::Error> {
+    let mut relation = get_relation(first, second).await?;
+    relation.stance = Stance::War;
+    relation.since = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    relation.proposal = None;
+    relation.proposed_by = "".to_string();
+    save_relation(relation).await
+}
+
+/// Gets every saved relation a faction is part of. Creates its own database connection
+///
+/// # Arguments
+///
+/// * `tag` - The tag of the faction
+///
+/// # Returns
+/// ```Vec<Relation>```: All of the faction's relations
+///
+pub(crate) async fn get_all_by_faction(
+    tag: String,
+) -> Result<Vec<Relation>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let filter = doc! {"$or": [{"faction_a": tag.clone()}, {"faction_b": tag}]};
+    let cursor = db
+        .collection::<Relation>("relations")
+        .find(filter, None)
+        .await?;
+    let all: Vec<Relation> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+/// Gets the tags of every faction allied with the given faction
+///
+/// # Arguments
+///
+/// * `tag` - The tag of the faction
+///
+/// # Returns
+/// ```Vec<String>```: The tags of the faction's allies
+///
+pub(crate) async fn get_allies(tag: String) -> Result<Vec<String>, mongodb::error::Error> {
+    let relations = get_all_by_faction(tag.clone()).await?;
+    Ok(relations
+        .iter()
+        .filter(|relation| relation.stance == Stance::Alliance)
+        .map(|relation| relation.other(&tag))
+        .collect())
+}
+
+pub(crate) async fn get_all() -> Result<Vec<Relation>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let cursor = db
+        .collection::<Relation>("relations")
+        .find(None, None)
+        .await?;
+    let all: Vec<Relation> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+pub(crate) async fn internal_delete_relation(
+    db: &Database,
+    relation: Relation,
+) -> Result<(), mongodb::error::Error> {
+    let filter = doc! {"faction_a": relation.faction_a, "faction_b": relation.faction_b};
+    db.collection::<Relation>("relations")
+        .delete_one(filter, None)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use futures::TryStreamExt;
+use lazy_static::lazy_static;
+use mongodb::bson::{doc, Bson};
+use mongodb::options::{FindOptions, ReplaceOptions};
+use mongodb::{Database, IndexModel};
+
+use crate::db;
+use crate::types::map::Snapshot;
+
+// How many hours of snapshots are kept around for timelapses
+pub(crate) const SNAPSHOT_HOURS: u64 = 72;
+// How many seconds there are between snapshots
+pub(crate) const SNAPSHOT_INTERVAL: u64 = 30 * 60;
+
+/// Sets up the indexes timelapses need to find the history of an area quickly. Safe to run every
+/// time the bot starts, since existing indexes are left alone
+pub(crate) async fn create_indexes() -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let collection = db.collection::<Snapshot>("snapshots");
+    collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"x": 1, "y": 1, "taken_at": 1})
+                .build(),
+            None,
+        )
+        .await?;
+    collection
+        .create_index(
+            IndexModel::builder().keys(doc! {"taken_at": 1}).build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// Who owned each tile at some point, with an empty tag for tiles nobody owned any more
+type Owners = HashMap<(i32, i32), String>;
+
+lazy_static! {
+    // Who owned each tile as of the last snapshot this bot took, and when that was. Kept around so
+    // each snapshot only has to read what has been recorded since
+    static ref RECORDED: Mutex<Option<(u64, Owners)>> = Mutex::new(None);
+}
+
+/// Records every tile that has changed hands since the last snapshot, so the map can be played
+/// back later. Creates its own database connection
+pub(crate) async fn record_ownership() -> Result<(), mongodb::error::Error> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let db = db::get_db().await?;
+    // Snapshots share the x, y and faction fields with tiles, so only those are read
+    let options = FindOptions::builder()
+        .projection(doc! {"_id": 0, "x": 1, "y": 1, "faction": 1})
+        .build();
+    let current: HashMap<(i32, i32), String> = db
+        .collection::<Snapshot>("tiles")
+        .find(doc! {"faction": {"$ne": ""}}, options)
+        .await?
+        .try_collect::<Vec<Snapshot>>()
+        .await?
+        .into_iter()
+        .map(|owned| ((owned.x, owned.y), owned.faction))
+        .collect();
+    // The first snapshot after starting up reads the starting owners and every change since, and
+    // after that only what was recorded after the last snapshot is read
+    let cached = RECORDED.lock().unwrap().take();
+    let (after, mut previous) = cached.unwrap_or_default();
+    let newer = internal_get_after(&db, after).await?;
+    previous.extend(replay(&newer, &[now]).remove(0));
+    let changes = ownership_changes(&previous, &current, now);
+    if !changes.is_empty() {
+        db.collection::<Snapshot>("snapshots")
+            .insert_many(changes, None)
+            .await?;
+    }
+    *RECORDED.lock().unwrap() = Some((now, current));
+    Ok(())
+}
+
+// Gets every snapshot taken after a given time, oldest first. Starting owners are taken at time 0,
+// so passing 0 gets them as well as every change
+async fn internal_get_after(
+    db: &Database,
+    after: u64,
+) -> Result<Vec<Snapshot>, mongodb::error::Error> {
+    let filter = if after == 0 {
+        doc! {}
+    } else {
+        doc! {"taken_at": {"$gt": after as i64}}
+    };
+    let options = FindOptions::builder()
+        .sort(doc! {"taken_at": 1})
+        .projection(doc! {"_id": 0})
+        .build();
+    let cursor = db
+        .collection::<Snapshot>("snapshots")
+        .find(filter, options)
+        .await?;
+    let all: Vec<Snapshot> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+/// Gets the times every snapshot since a given time was taken. Creates its own database connection
+///
+/// # Arguments
+///
+/// * `since` - The earliest epoch time to include
+///
+/// # Returns
+/// ```Vec<u64>```: The times, oldest first. Only times when something changed hands are included
+///
+pub(crate) async fn get_times(since: u64) -> Result<Vec<u64>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let values = db
+        .collection::<Snapshot>("snapshots")
+        .distinct("taken_at", doc! {"taken_at": {"$gte": since as i64}}, None)
+        .await?;
+    let mut times: Vec<u64> = values
+        .into_iter()
+        .filter_map(|value| match value {
+            Bson::Int64(time) => Some(time as u64),
+            Bson::Int32(time) => Some(time as u64),
+            _ => None,
+        })
+        .collect();
+    times.sort();
+    Ok(times)
+}
+
+/// Gets the whole recorded history of the tiles in an area. Creates its own database connection
+///
+/// # Arguments
+///
+/// * `x_range` - The minimum and maximum x values of the area
+/// * `y_range` - The minimum and maximum y values of the area
+///
+/// # Returns
+/// ```Vec<Snapshot>```: Every change of owner in the area, oldest first
+///
+pub(crate) async fn get_many(
+    x_range: (i32, i32),
+    y_range: (i32, i32),
+) -> Result<Vec<Snapshot>, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let filter = doc! {
+        "x": {"$gte": x_range.0, "$lte": x_range.1},
+        "y": {"$gte": y_range.0, "$lte": y_range.1},
+    };
+    let options = FindOptions::builder()
+        .sort(doc! {"taken_at": 1})
+        .projection(doc! {"_id": 0})
+        .build();
+    let cursor = db
+        .collection::<Snapshot>("snapshots")
+        .find(filter, options)
+        .await?;
+    let all: Vec<Snapshot> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+/// Folds every change from before a given time into a single snapshot per tile taken at time 0,
+/// which holds who owned the tile before the history starts. Creates its own database connection
+pub(crate) async fn compact_before(before: u64) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let collection = db.collection::<Snapshot>("snapshots");
+    let filter = doc! {"taken_at": {"$gt": 0, "$lt": before as i64}};
+    let options = FindOptions::builder()
+        .sort(doc! {"taken_at": 1})
+        .projection(doc! {"_id": 0})
+        .build();
+    let old: Vec<Snapshot> = collection
+        .find(filter.clone(), options)
+        .await?
+        .try_collect()
+        .await?;
+    if old.is_empty() {
+        return Ok(());
+    }
+    // The new starting owners are written before the changes are deleted, so stopping halfway
+    // never loses track of who owned a tile
+    let upsert = ReplaceOptions::builder().upsert(true).build();
+    for ((x, y), faction) in replay(&old, &[before]).remove(0) {
+        let start = doc! {"x": x, "y": y, "taken_at": 0};
+        if faction.is_empty() {
+            collection.delete_one(start, None).await?;
+        } else {
+            let snapshot = Snapshot {
+                x,
+                y,
+                faction,
+                taken_at: 0,
+            };
+            collection
+                .replace_one(start, snapshot, upsert.clone())
+                .await?;
+        }
+    }
+    collection.delete_many(filter, None).await?;
+    Ok(())
+}
+
+/// Works out which tiles have changed hands between two snapshots of who owns what
+///
+/// # Arguments
+///
+/// * `previous` - Who owned each tile at the last snapshot. An empty tag means nobody did
+/// * `current` - Who owns each tile now
+/// * `now` - The current epoch time
+///
+/// # Returns
+/// ```Vec<Snapshot>```: A snapshot for every tile with a new owner, with an empty tag for tiles
+/// that nobody owns any more
+///
+pub(crate) fn ownership_changes(
+    previous: &HashMap<(i32, i32), String>,
+    current: &HashMap<(i32, i32), String>,
+    now: u64,
+) -> Vec<Snapshot> {
+    let mut changes = Vec::new();
+    for (&(x, y), faction) in current {
+        if previous.get(&(x, y)) != Some(faction) {
+            changes.push(Snapshot {
+                x,
+                y,
+                faction: faction.clone(),
+                taken_at: now,
+            });
+        }
+    }
+    for (&(x, y), faction) in previous {
+        if !faction.is_empty() && !current.contains_key(&(x, y)) {
+            changes.push(Snapshot {
+                x,
+                y,
+                faction: "".to_string(),
+                taken_at: now,
+            });
+        }
+    }
+    changes
+}
+
+/// Plays back a history of changes to work out who owned each tile at different times
+///
+/// # Arguments
+///
+/// * `changes` - The changes of owner, oldest first
+/// * `times` - The times to find the owners at, oldest first
+///
+/// # Returns
+/// ```Vec<HashMap<(i32, i32), String>>```: Who owned each tile at each time. An empty tag means
+/// nobody owned the tile any more
+///
+pub(crate) fn replay(changes: &[Snapshot], times: &[u64]) -> Vec<HashMap<(i32, i32), String>> {
+    let mut owners = HashMap::new();
+    let mut changes = changes.iter().peekable();
+    let mut replayed = Vec::new();
+    for &time in times {
+        while let Some(change) = changes.next_if(|change| change.taken_at <= time) {
+            owners.insert((change.x, change.y), change.faction.clone());
+        }
+        replayed.push(owners.clone());
+    }
+    replayed
+}
@@ -1,11 +1,12 @@
 use futures::stream::TryStreamExt;
-use mongodb::bson::doc;
+use mongodb::bson::{doc, from_document, Bson, Document};
 use mongodb::options::FindOptions;
 use mongodb::Database;
 
 use crate::db;
-use crate::image::VIEW_DISTANCE;
+use crate::render_cache;
 use crate::types::map::Tile;
+use crate::vision::{Visibility, SCOUT_SIGHT_RANGE};
 
 /// Gets a tile. Uses a pre-existing database connection
 ///
@@ -57,11 +58,15 @@ pub(crate) async fn internal_set_tile(
 ) -> Result<(), mongodb::error::Error> {
     let collection = db.collection::<Tile>("tiles");
     let filter = doc! {"x": tile.x, "y": tile.y};
+    let (x, y) = (tile.x, tile.y);
     if !internal_check_tile(&db, tile.x, tile.y).await? {
         collection.insert_one(tile, None).await?;
     } else {
         collection.find_one_and_replace(filter, tile, None).await?;
     }
+    // Only once the write has landed, otherwise a map drawn in between would read the old tile
+    // but be cached against the new revision
+    render_cache::invalidate_tile(x, y);
     Ok(())
 }
 
@@ -331,37 +336,52 @@ pub async fn invert_y(tiles: Vec<Vec<Tile>>) -> Vec<Vec<Tile>> {
     new_tiles
 }
 
-/// Checks to see if a faction is able to see a tile. This just boils down to checking if there are
-/// any tiles in VIEW_DISTANCE of the specified tile that are owned by that faction
+/// Works out which tiles a faction can see. Every tile it owns lets it see the tiles around it,
+/// and tiles with scouts on them see much further
+///
+/// # Arguments
+///
+/// * `faction` - The faction trying to see
+///
+/// # Returns
+/// ```Visibility```: The tiles the faction can see, and the ones it can see enemy units on
+///
+pub async fn get_visibility(faction: String) -> Result<Visibility, mongodb::error::Error> {
+    if faction.is_empty() {
+        return Ok(Visibility::default());
+    }
+    let territory = get_all_by_faction(faction).await?;
+    Ok(Visibility::from_territory(&territory))
+}
+
+/// Checks to see if any of a group of factions is able to see a tile. Only tiles close enough to
+/// see it from are looked at, rather than the whole of each faction's territory
 ///
 /// # Arguments
 ///
 /// * `x` - The x value of the tile
 /// * `y` - The y value of the tile
-/// * `faction` - The faction trying to see the tile
+/// * `factions` - The factions trying to see the tile, like a faction and its allies
 ///
 /// # Returns
-/// ```boolean```: Whether or not that faction is allowed to see the tile
+/// ```boolean```: Whether or not any of those factions is allowed to see the tile
 ///
 
-pub async fn can_faction_see(
+pub async fn can_factions_see(
     x: i32,
     y: i32,
-    faction: String,
+    factions: Vec<String>,
 ) -> Result<bool, mongodb::error::Error> {
-    let x_range = (x - VIEW_DISTANCE, x + VIEW_DISTANCE);
-    let y_range = (y - VIEW_DISTANCE, y + VIEW_DISTANCE);
-    let any_tiles_in_range = !any_exist(x_range, y_range).await?;
-    if !any_tiles_in_range {
-        return Ok(false);
-    }
-    let tiles = get_many(x_range, y_range).await?;
-    for tile in tiles {
-        if tile.faction == faction {
-            return Ok(true);
-        }
-    }
-    Ok(false)
+    let db = db::get_db().await?;
+    // Nothing sees further than a scout, so anything beyond that can't help
+    let filter = doc! {
+        "faction": {"$in": factions},
+        "x": {"$gte": x - SCOUT_SIGHT_RANGE, "$lte": x + SCOUT_SIGHT_RANGE},
+        "y": {"$gte": y - SCOUT_SIGHT_RANGE, "$lte": y + SCOUT_SIGHT_RANGE},
+    };
+    let cursor = db.collection::<Tile>("tiles").find(filter, None).await?;
+    let nearby: Vec<Tile> = cursor.try_collect().await?;
+    Ok(Visibility::from_territory(&nearby).can_see(x, y))
 }
 
 pub async fn internal_delete_tile(
@@ -373,10 +393,38 @@ pub async fn internal_delete_tile(
     db.collection::<Tile>("tiles")
         .delete_one(filter, None)
         .await?;
+    render_cache::invalidate_tile(x, y);
     Ok(())
 }
 
 pub async fn delete_tile(x: i32, y: i32) -> Result<(), mongodb::error::Error> {
     let db = db::get_db().await?;
     Ok(internal_delete_tile(&db, x, y).await?)
+}
+
+/// Rewrites tiles saved before buildings had levels, so every building count is stored per level.
+/// Old tiles can still be read without this, but it keeps the database in one shape
+///
+/// # Returns
+/// ```Result<u32, mongodb::error::Error>```: How many tiles were migrated
+///
+pub async fn migrate_building_levels() -> Result<u32, mongodb::error::Error> {
+    let db = db::get_db().await?;
+    let cursor = db.collection::<Document>("tiles").find(None, None).await?;
+    let all: Vec<Document> = cursor.try_collect().await?;
+    let mut migrated = 0;
+    for document in all {
+        let outdated = match document.get_document("buildings") {
+            Ok(buildings) => buildings
+                .iter()
+                .any(|(_, count)| !matches!(count, Bson::Array(_))),
+            Err(_) => false,
+        };
+        if outdated {
+            let tile: Tile = from_document(document)?;
+            internal_set_tile(&db, tile).await?;
+            migrated += 1;
+        }
+    }
+    Ok(migrated)
 }
\ No newline at end of file
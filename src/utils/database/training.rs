@@ -0,0 +1,102 @@
+use std::time::SystemTime;
+
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::Database;
+
+use crate::db;
+use crate::types::units::TrainingOrder;
+
+/// Queues up a training order. Creates its own database connection
+///
+/// # Arguments
+///
+/// * `order` - The order to queue
+///
+/// # Returns
+/// ```Result```: The result of the operation
+///
+pub(crate) async fn add_order(order: TrainingOrder) -> Result<(), mongodb::error::Error> {
+    let db = db::get_db().await?;
+    db.collection::<TrainingOrder>("training")
+        .insert_one(order, None)
+        .await?;
+    Ok(())
+}
+
+/// Gets every training order that has finished. Uses a pre-existing database connection
+///
+/// # Arguments
+///
+/// * `db` - A database connection
+/// * `now` - The current epoch time
+///
+/// # Returns
+/// ```Vec<TrainingOrder>```: The orders whose units are ready
+///
+pub(crate) async fn internal_get_ready(
+    db: &Database,
+    now: u64,
+) -> Result<Vec<TrainingOrder>, mongodb::error::Error> {
+    let filter = doc! {"ready_at": {"$lte": now as i64}};
+    let cursor = db
+        .collection::<TrainingOrder>("training")
+        .find(filter, None)
+        .await?;
+    let all: Vec<TrainingOrder> = cursor.try_collect().await?;
+    Ok(all)
+}
+
+/// Deletes a training order. Uses a pre-existing database connection
+///
+/// # Arguments
+///
+/// * `db` - A database connection
+/// * `id` - The ID of the order
+///
+/// # Returns
+/// ```bool```: Whether there was an order to delete
+///
+pub(crate) async fn internal_delete_order(
+    db: &Database,
+    id: String,
+) -> Result<bool, mongodb::error::Error> {
+    let result = db
+        .collection::<TrainingOrder>("training")
+        .delete_one(doc! {"id": id}, None)
+        .await?;
+    Ok(result.deleted_count > 0)
+}
+
+/// Moves every finished training order onto its tile. If the faction has lost the tile in the
+/// meantime, the recruits scatter and the order is dropped
+pub async fn complete_ready() -> Result<(), mongodb::error::Error> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let db = db::get_db().await?;
+    for order in internal_get_ready(&db, now).await? {
+        // Another pass already handed these units out
+        if !internal_delete_order(&db, order.id.clone()).await? {
+            continue;
+        }
+        let mut tile = db::tiles::internal_get_tile(&db, order.x, order.y).await?;
+        if tile.faction == order.faction {
+            let existing = tile.units.get(&order.unit).unwrap_or(&0);
+            tile.units
+                .insert(order.unit.clone(), existing + order.amount);
+            db::tiles::internal_set_tile(&db, tile).await?;
+        } else {
+            warn!(
+                "{} lost {}, {} before their {} {} finished training",
+                order.faction,
+                order.x,
+                order.y,
+                order.amount,
+                order.unit.data().name
+            );
+        }
+    }
+    Ok(())
+}
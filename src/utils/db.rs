@@ -1,21 +1,56 @@
+use std::collections::HashMap;
 use std::time::SystemTime;
 
 use mongodb::options::ClientOptions;
 use mongodb::{Client, Database};
+use rand::Rng;
 
+use crate::config::get_config;
 use crate::conversions::day_to_seconds;
 use crate::db::tiles::get_all_by_faction;
+use crate::terrain::TerrainGenerator;
 use crate::types::buildings::Building;
-use crate::types::factions::Production;
+use crate::types::factions::{Faction, Production, DEFAULT_TAX_RATE};
+use crate::types::units::Unit;
 use crate::{db, Error};
 
+// How much money each person would pay in a day if they were taxed at 100%
+const TAX_PER_PERSON: f32 = 5.0;
+// How many points of happiness each percent of tax above the default costs
+const TAX_UNHAPPINESS: f64 = 0.5;
+// The share of unfed or unpaid troops that desert each hour
+const DESERTION_RATE: f64 = 0.2;
+// How much food each person eats in a day
+const FOOD_PER_PERSON: f32 = 0.5;
+// How many people die for every kg of food the faction is short
+const STARVATION_RATE: f64 = 0.1;
+// The happiness a faction sits at with nothing good or bad going on. Income and growth are
+// unchanged at this level, doubled at 100 and stopped at 0
+const NEUTRAL_HAPPINESS: f64 = 50.0;
+// Roughly how many seconds it takes for happiness to settle after things change
+const HAPPINESS_SETTLE_TIME: f64 = 60.0 * 60.0;
+
+#[path = "database/armies.rs"]
+pub(crate) mod armies;
 #[path = "database/cleaners.rs"]
 pub mod cleaners;
+#[path = "database/construction.rs"]
+pub(crate) mod construction;
 #[path = "database/factions.rs"]
 pub(crate) mod factions;
+#[path = "database/intel.rs"]
+pub(crate) mod intel;
+#[path = "database/movements.rs"]
+pub(crate) mod movements;
+#[path = "database/relations.rs"]
+pub(crate) mod relations;
+#[path = "database/snapshots.rs"]
+pub(crate) mod snapshots;
 #[path = "database/tiles.rs"]
 #[allow(dead_code)]
 pub(crate) mod tiles;
+#[path = "database/training.rs"]
+pub(crate) mod training;
 #[path = "database/users.rs"]
 pub(crate) mod users;
 
@@ -36,65 +71,104 @@ pub async fn get_db() -> Result<Database, mongodb::error::Error> {
 
 pub async fn build_production() -> Result<(), Error> {
     let factions = factions::get_all().await?;
-    for mut faction in factions {
-        let all_tiles = get_all_by_faction(faction.tag.clone()).await?;
-        let faction_production = faction.production.clone();
-        let mut production = Production {
-            money: faction_production.money,
-            money_per_second: 0.0,
-            population: faction_production.population,
-            population_per_second: 0.0,
-            food: faction_production.food,
-            food_per_second: 0.0,
-            wood: faction_production.wood,
-            wood_per_second: 0.0,
-            metal: faction_production.metal,
-            metal_per_second: 0.0,
-            population_cap: 0,
-            happiness: faction_production.happiness,
-            last_updated: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
-        };
-        for tile in all_tiles {
-            for (building, amount) in tile.buildings {
-                use Building::*;
-                match building {
-                    Farm => {
-                        production.food_per_second += (amount as f64) * day_to_seconds(15.0) as f64
-                    }
-                    Mill => {
-                        production.wood_per_second += (amount as f64) * day_to_seconds(2.0) as f64
-                    }
-                    Blacksmith => {
-                        production.metal_per_second += (amount as f64) * day_to_seconds(2.0) as f64
-                    }
-                    Capital => {
-                        production.population_per_second += day_to_seconds(2.0) as f64;
-                        production.population_cap += 100;
-                        production.food_per_second += day_to_seconds(90.0) as f64;
-                        production.wood_per_second += day_to_seconds(20.0) as f64;
-                        production.metal_per_second += day_to_seconds(20.0) as f64;
-                    }
-                    House => {
-                        production.population_per_second +=
-                            day_to_seconds(2.0) as f64 * amount as f64;
-                        production.population_cap += 5 * amount as i64;
-                    }
+    for faction in factions {
+        build_faction_production(faction).await?;
+    }
+    Ok(())
+}
 
-                    Hut => {
-                        production.population_per_second +=
-                            day_to_seconds(1.0) as f64 * amount as f64;
-                        production.population_cap += 3 * amount as i64;
-                    }
-                    _ => {}
+/// Recalculates a single faction's production stats from its tiles and saves them. Used by the
+/// background loop, and by commands that need the new stats to apply straight away
+///
+/// # Arguments
+///
+/// * `faction` - The faction to rebuild
+///
+/// # Returns
+/// ```Result<(), Error>```: The result of the operation
+///
+pub async fn build_faction_production(mut faction: Faction) -> Result<(), Error> {
+    let all_tiles = get_all_by_faction(faction.tag.clone()).await?;
+    let faction_production = faction.production.clone();
+    let mut production = Production {
+        money: faction_production.money,
+        money_per_second: 0.0,
+        population: faction_production.population,
+        population_per_second: 0.0,
+        food: faction_production.food,
+        food_per_second: 0.0,
+        wood: faction_production.wood,
+        wood_per_second: 0.0,
+        metal: faction_production.metal,
+        metal_per_second: 0.0,
+        population_cap: 0,
+        happiness: faction_production.happiness,
+        tax_rate: faction_production.tax_rate,
+        last_updated: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    // Workplaces can't run without money to pay the workers, so an empty treasury shuts them
+    // down until it is topped back up
+    let shut_down = faction_production.money <= 0.0;
+    let terrain = TerrainGenerator::new(get_config().perlin_seed);
+    for tile in all_tiles {
+        let deposit = terrain.deposit_at(tile.x, tile.y);
+        for (unit, amount) in tile.units {
+            let data = unit.data();
+            production.food_per_second -=
+                amount as f64 * day_to_seconds(data.food_upkeep as f32) as f64;
+            production.money_per_second -=
+                amount as f64 * day_to_seconds(data.money_upkeep as f32) as f64;
+        }
+        for (building, levels) in tile.buildings {
+            use Building::*;
+            if shut_down && matches!(building, Farm | Mill | Blacksmith | Barracks) {
+                continue;
+            }
+            let amount = levels.total();
+            // Upgraded buildings count for more than one building when it comes to output, and
+            // deposits on the tile boost the buildings that use them
+            let mut output = levels.output();
+            if let Some(deposit) = deposit.filter(|deposit| deposit.boosts(&building)) {
+                output *= deposit.data().multiplier;
+            }
+            production.money_per_second -=
+                amount as f64 * day_to_seconds(building.data().upkeep as f32) as f64;
+            match building {
+                Farm => production.food_per_second += output * day_to_seconds(15.0) as f64,
+                Mill => production.wood_per_second += output * day_to_seconds(2.0) as f64,
+                Blacksmith => production.metal_per_second += output * day_to_seconds(2.0) as f64,
+                Capital => {
+                    production.population_per_second += day_to_seconds(2.0) as f64;
+                    production.population_cap += 100;
+                    production.food_per_second += day_to_seconds(90.0) as f64;
+                    production.wood_per_second += day_to_seconds(20.0) as f64;
+                    production.metal_per_second += day_to_seconds(20.0) as f64;
+                }
+                House => {
+                    production.population_per_second += day_to_seconds(2.0) as f64 * output;
+                    production.population_cap += (5.0 * output) as i64;
                 }
+
+                Hut => {
+                    production.population_per_second += day_to_seconds(1.0) as f64 * output;
+                    production.population_cap += (3.0 * output) as i64;
+                }
+                _ => {}
             }
         }
-        faction.production = production;
-        factions::save_faction(faction).await?;
     }
+    // Everyone pays their taxes
+    production.money_per_second += production.population
+        * (production.tax_rate / 100.0)
+        * day_to_seconds(TAX_PER_PERSON) as f64;
+    // Everyone has to eat
+    production.food_per_second -=
+        (production.population * day_to_seconds(FOOD_PER_PERSON) as f64).max(0.0);
+    faction.production = production;
+    factions::save_faction(faction).await?;
     Ok(())
 }
 
@@ -106,16 +180,132 @@ pub async fn update_economy() -> Result<(), Error> {
     let factions = factions::get_all().await?;
     let conn = db::get_db().await?;
     for mut faction in factions {
-        let mut production = faction.production;
-        let time_difference = (current_epoch - production.last_updated) as f64;
-        production.money += (production.money_per_second * time_difference) as f32;
-        production.food += (production.food_per_second * time_difference) as f32;
-        production.wood += (production.wood_per_second * time_difference) as f32;
-        production.metal += (production.metal_per_second * time_difference) as f32;
-        production.population += (production.population_per_second * time_difference) as f64;
+        let time_difference = (current_epoch - faction.production.last_updated) as f64;
+        let mut production = tick_production(faction.production, time_difference);
         production.last_updated = current_epoch;
+        if production.food <= 0.0 || production.money <= 0.0 {
+            // Troops that aren't being fed or paid start to wander off
+            let fraction = (DESERTION_RATE * time_difference / (60.0 * 60.0)).min(1.0);
+            let mut rng = rand::rngs::OsRng;
+            for mut tile in get_all_by_faction(faction.tag.clone()).await? {
+                let remaining = desert(&tile.units, fraction, &mut rng);
+                if remaining != tile.units {
+                    tile.units = remaining;
+                    tiles::internal_set_tile(&conn, tile).await?;
+                }
+            }
+        }
         faction.production = production;
         factions::internal_save_faction(&conn, faction).await?;
     }
     Ok(())
+}
+
+/// Works out what a faction's happiness is heading towards, based on how well fed, how crowded and
+/// how heavily taxed its people are
+///
+/// # Arguments
+///
+/// * `production` - The faction's current production stats
+///
+/// # Returns
+/// ```f64```: The happiness the faction will settle at, from 0 to 100
+///
+pub(crate) fn target_happiness(production: &Production) -> f64 {
+    let mut happiness = NEUTRAL_HAPPINESS;
+    if production.food <= 0.0 {
+        // Starving people are not happy people
+        happiness -= 40.0;
+    } else if production.food_per_second >= 0.0 {
+        happiness += 20.0;
+    } else {
+        happiness -= 10.0;
+    }
+    happiness -= (production.tax_rate - DEFAULT_TAX_RATE) * TAX_UNHAPPINESS;
+    if production.population_cap > 0 {
+        let crowding = production.population / production.population_cap as f64;
+        if crowding > 0.9 {
+            happiness -= (crowding - 0.9) * 200.0;
+        }
+    }
+    happiness.clamp(0.0, 100.0)
+}
+
+/// Advances a faction's resources by a number of seconds. Happiness drifts towards its target and
+/// scales income and population growth, population is held under the cap, and running out of food
+/// kills people off
+///
+/// # Arguments
+///
+/// * `production` - The faction's production stats before the tick
+/// * `time_difference` - How many seconds have passed since the last tick
+///
+/// # Returns
+/// ```Production```: The production stats after the tick
+///
+pub(crate) fn tick_production(production: Production, time_difference: f64) -> Production {
+    let mut production = production;
+    let settle = (time_difference / HAPPINESS_SETTLE_TIME).min(1.0);
+    production.happiness += (target_happiness(&production) - production.happiness) * settle;
+    production.happiness = production.happiness.clamp(0.0, 100.0);
+    let modifier = production.happiness / NEUTRAL_HAPPINESS;
+
+    production.money += (production.money_per_second * modifier * time_difference) as f32;
+    production.food += (production.food_per_second * time_difference) as f32;
+    production.wood += (production.wood_per_second * time_difference) as f32;
+    production.metal += (production.metal_per_second * time_difference) as f32;
+    if production.money < 0.0 {
+        // The treasury can't go into debt, the bills just go unpaid
+        production.money = 0.0;
+    }
+    if production.food < 0.0 {
+        // Nobody grows while people are starving, and the food that wasn't there costs lives
+        production.population += production.food as f64 * STARVATION_RATE;
+        production.food = 0.0;
+    } else {
+        production.population += production.population_per_second * modifier * time_difference;
+    }
+    let cap = production.population_cap as f64;
+    if production.population > cap {
+        production.population = cap;
+    }
+    if production.population < 0.0 {
+        production.population = 0.0;
+    }
+    production
+}
+
+/// Works out which units are left after some of them desert. Units without any upkeep never desert,
+/// since they aren't owed anything
+///
+/// # Arguments
+///
+/// * `units` - The units on a tile
+/// * `fraction` - The share of each unit type that deserts
+/// * `rng` - Used to decide whether a partial unit deserts, so small armies still shrink over time
+///
+/// # Returns
+/// ```HashMap<Unit, u32>```: The units that stayed
+///
+pub(crate) fn desert(
+    units: &HashMap<Unit, u32>,
+    fraction: f64,
+    rng: &mut impl Rng,
+) -> HashMap<Unit, u32> {
+    let mut remaining = HashMap::new();
+    for (unit, amount) in units {
+        let data = unit.data();
+        let mut lost = 0;
+        if data.food_upkeep > 0 || data.money_upkeep > 0 {
+            let expected = *amount as f64 * fraction;
+            lost = expected.floor() as u32;
+            if rng.gen::<f64>() < expected.fract() {
+                lost += 1;
+            }
+        }
+        if *amount > lost {
+            remaining.insert(unit.clone(), amount - lost);
+        }
+    }
+    remaining
 }
\ No newline at end of file
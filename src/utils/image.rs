@@ -1,24 +1,92 @@
+use std::collections::HashMap;
+use std::io::Cursor;
 use std::thread;
+use std::time::SystemTime;
 
-use atomic_counter::{AtomicCounter, RelaxedCounter};
-use dashmap::DashMap;
-use image::{GenericImage, GenericImageView, ImageBuffer, Rgb, RgbImage};
-use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut, draw_text_mut};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{resize, FilterType};
+use image::{
+    Delay, DynamicImage, Frame, GenericImage, ImageBuffer, ImageOutputFormat, Rgb, RgbImage,
+    RgbaImage,
+};
+use imageproc::drawing::{
+    draw_filled_circle_mut, draw_filled_rect_mut, draw_polygon_mut, draw_text_mut,
+};
+use imageproc::point::Point;
 use imageproc::rect::Rect;
+use lazy_static::lazy_static;
 use noise::{Clamp, NoiseFn, OpenSimplex};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use rusttype::{Font, Scale};
 use serde_json::to_string;
 
 use crate::config::get_config;
+use crate::conversions::hsv_to_rgb;
+use crate::render_cache;
+use crate::render_cache::Shade;
+use crate::terrain::TerrainGenerator;
 use crate::types;
+use crate::types::buildings::Building;
+use crate::types::factions::tag_colour;
+use crate::types::terrain::Deposit;
+use crate::types::units::Movement;
+use crate::vision::{format_age, Visibility};
+use crate::MapIcons;
 
 const TILE_SIZE: i32 = 150;
 const BORDER_SIZE: i32 = 5;
 const INSET_SIZE: i32 = 10;
 const IN_TILE_SIZE: i32 = TILE_SIZE - (INSET_SIZE * 2);
-pub const VIEW_DISTANCE: i32 = 10;
+
+// How many tiles out from the centre a map reaches, so a radius of 5 gives an 11x11 map
+pub const DEFAULT_MAP_RADIUS: i32 = 5;
+pub const MAX_MAP_RADIUS: i32 = 10;
+// Maps are drawn at TILE_SIZE and then scaled down, so tiles can't be drawn any bigger than that
+pub const DEFAULT_TILE_PX: u32 = TILE_SIZE as u32;
+const MIN_TILE_PX: u32 = 40;
+// Big maps are scaled down until they fit in this many pixels across, to keep the upload small
+const MAX_MAP_PX: u32 = 2400;
+
+const DEPOSIT_MARKER_SIZE: i32 = 10;
 
 const TEXT_SCALE: f32 = 75.0;
+const AGE_TEXT_SCALE: f32 = 40.0;
+
+const ARROW_WIDTH: f32 = 8.0;
+const ARROW_HEAD_SIZE: f32 = 30.0;
+const BADGE_RADIUS: i32 = 24;
+
+// Your own territory is always drawn in the same colour, everyone else's in their own colour
+const OWN_COLOUR: [u8; 3] = [102, 178, 255];
+// How thick the line between two factions' territory is on each side of the edge
+const FACTION_BORDER_SIZE: i32 = 3;
+
+const LEGEND_TEXT_SCALE: f32 = 40.0;
+const LEGEND_SWATCH_SIZE: i32 = 36;
+const LEGEND_ROW_HEIGHT: i32 = 60;
+const LEGEND_MARGIN: i32 = 10;
+
+// The overview map is always this many pixels across, so zooming out fits more tiles in
+const OVERVIEW_SIZE: i32 = 800;
+pub const DEFAULT_OVERVIEW_ZOOM: u32 = 4;
+
+// Timelapses have a lot of frames to draw and send, so their tiles are kept small
+pub const TIMELAPSE_TILE_PX: u32 = 60;
+pub const MAX_TIMELAPSE_FRAMES: usize = 24;
+// How long each frame is shown for in milliseconds. The last one stays up a bit longer
+const TIMELAPSE_FRAME_TIME: u32 = 500;
+const TIMELAPSE_HOLD_TIME: u32 = 2000;
+// Trades quality for speed when the GIF is encoded. 1 is the best and 30 is the fastest
+const GIF_SPEED: i32 = 10;
+
+const ICON_SIZE: i32 = 32;
+const COUNT_TEXT_SCALE: f32 = 26.0;
+// Icons are laid out in rows across the tile, buildings at the top and units underneath
+const BUILDING_ICONS_PER_ROW: i32 = 4;
+const MAX_BUILDING_ICONS: usize = 8;
+const UNIT_STACKS_PER_ROW: i32 = 2;
+const MAX_UNIT_STACKS: usize = 4;
 const LETTER_WIDTH: i32 = 30;
 
 // Since these are all constant, coords for the positions of the circles can be precalculated
@@ -39,234 +107,245 @@ const C_4_CENTER: (i32, i32) = (
     (INSET_SIZE + ((IN_TILE_SIZE / 4) * 3)),
 );
 
+// Maps with fewer tiles than this are quicker to draw on one thread than to split up
+const PARALLEL_TILES: usize = 25;
+const MAX_RENDER_THREADS: usize = 4;
+
+lazy_static! {
+    // Big maps have their rows drawn on this pool. It's kept small so a few maps being drawn at
+    // once can't take over every core the bot has
+    static ref RENDER_POOL: ThreadPool = ThreadPoolBuilder::new()
+        .num_threads(
+            thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1)
+                .min(MAX_RENDER_THREADS)
+        )
+        .thread_name(|index| format!("map-render-{}", index))
+        .build()
+        .expect("Failed to start the map rendering pool");
+    // The icons never change, so they are only decoded and scaled the first time a map is drawn
+    static ref ICONS: HashMap<String, RgbaImage> = load_icons();
+}
+
+/// Works out how many pixels across each tile of a map will be, keeping it within the limits and
+/// shrinking it if the map would otherwise be too big to send
+///
+/// # Arguments
+///
+/// * `requested` - How big the tiles were asked to be
+/// * `tiles_across` - How many tiles wide the map is
+///
+/// # Returns
+/// ```u32```: The size each tile will be drawn at
+///
+pub(crate) fn map_tile_px(requested: u32, tiles_across: u32) -> u32 {
+    // The coordinate gutter takes up one more tile's worth of space
+    let fits = MAX_MAP_PX / (tiles_across + 1);
+    requested.min(fits).clamp(MIN_TILE_PX, TILE_SIZE as u32)
+}
+
+/// Draws a map of the tiles as a faction sees them. Drawing takes a while, so it happens on a thread
+/// set aside for blocking work and the bot can keep answering commands in the meantime
+///
+/// # Arguments
+///
+/// * `grid` - The tiles on the map
+/// * `faction` - The faction looking at the map
+/// * `visibility` - What the faction can see and remembers
+/// * `movements` - Troop movements to draw arrows for
+/// * `colours` - The colour of each faction on the map
+/// * `tile_px` - How many pixels across each tile is
+///
+/// # Returns
+/// ```RgbImage```: The map, with the coordinates around the edge and a legend underneath
+///
 pub async fn draw_map(
-    grid: &Vec<Vec<types::map::Tile>>,
+    grid: &[Vec<types::map::Tile>],
     faction: String,
-) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-    // TODO: Optimise this to use imageproc stuff and also only take 1 pass
-    let img_width = (grid.clone().len() as i32 * TILE_SIZE) as u32;
-    let img_height = (grid.clone()[0].len() as i32 * TILE_SIZE) as u32;
+    visibility: &Visibility,
+    movements: &[Movement],
+    colours: &HashMap<String, [u8; 3]>,
+    tile_px: u32,
+) -> RgbImage {
+    let tiles: Vec<types::map::Tile> = grid.iter().flatten().cloned().collect();
+    let visibility = visibility.clone();
+    let movements = movements.to_vec();
+    let colours = colours.clone();
+    let seed = get_config().perlin_seed;
+    tokio::task::spawn_blocking(move || {
+        render_map(
+            tiles,
+            &faction,
+            &visibility,
+            &movements,
+            &colours,
+            tile_px,
+            seed,
+        )
+    })
+    .await
+    .expect("Failed to draw map")
+}
 
-    let mut image = RgbImage::new(img_width, img_height);
-    draw_filled_rect_mut(
-        &mut image,
-        Rect::at(0, 0).of_size(img_width, img_height),
-        Rgb([255, 255, 255]),
-    );
-    let perlin = OpenSimplex::new(get_config().perlin_seed); // Noise so we can texture the background
+// Does the actual drawing for draw_map. Every row of tiles is drawn straight into its own band of
+// the finished image, and big maps have their rows drawn in parallel
+pub(crate) fn render_map(
+    flat_grid: Vec<types::map::Tile>,
+    faction: &str,
+    visibility: &Visibility,
+    movements: &[Movement],
+    colours: &HashMap<String, [u8; 3]>,
+    tile_px: u32,
+    seed: u32,
+) -> RgbImage {
+    let perlin = OpenSimplex::new(seed); // Noise so we can texture the background
+    let terrain = TerrainGenerator::new(seed);
     let clamped = Clamp::new(perlin).set_bounds(0.1, 0.5);
 
     let scale = Scale {
         x: TEXT_SCALE,
         y: TEXT_SCALE,
     };
-    let completed_tiles: DashMap<(i32, i32), ImageBuffer<Rgb<u8>, Vec<u8>>> = DashMap::new();
-    // Dictionary of tiles that have been completed so we can store them in any order
 
     let font_bytes = Vec::from(include_bytes!("../font.ttf") as &[u8]);
     let font = Font::try_from_vec(font_bytes).unwrap();
-    let flat_grid = Vec::from_iter(grid.iter().flatten().cloned());
-    // Since everything is based on the X,Y of the tile, no point in setting up nested loops
-    let final_amount = flat_grid.len();
 
     let center_tile = flat_grid[flat_grid.len() / 2].clone();
-    let completed = RelaxedCounter::new(0);
-    thread::scope(|s| {
-        // imma be honest, idk what a scope is but it makes my IDE stop screaming at me so we'll use it
-        for tile in flat_grid.clone() {
-            // This seems bad but it works so ¯\_(ツ)_/¯
-            let completed_tiles = &completed_tiles;
-            let clamped = &clamped;
-            let completed = &completed;
-            let faction = &faction;
-            s.spawn(move || {
-                let mut tile_image = RgbImage::new((TILE_SIZE) as u32, (TILE_SIZE) as u32);
-                // Create a new image for each tile
-                for x in 0..TILE_SIZE {
-                    for y in 0..TILE_SIZE {
-                        let abs_x = (tile.x * TILE_SIZE) + x;
-                        let abs_y = (tile.y * TILE_SIZE) + y;
-                        let noise = clamped.get([abs_x as f64 / 100.0, abs_y as f64 / 100.0]);
-                        let hsv = hsv_to_rgb(143.0, 96.0, noise as f32);
-                        tile_image.put_pixel(x as u32, y as u32, Rgb([hsv.0, hsv.1, hsv.2]));
-                    }
-                }
+    // Who owns each tile, so tiles can tell where their faction's territory ends
+    let owners: HashMap<(i32, i32), String> = flat_grid
+        .iter()
+        .map(|tile| ((tile.x, tile.y), tile.faction.clone()))
+        .collect();
 
-                // TODO: add images. Make sure they are flipped so i can cause myself more issues later on :)
-
-                if tile.x == center_tile.x && tile.y == center_tile.y {
-                    // Draw a little signifier for the centre tile
-                    let offset_start = TILE_SIZE / 4;
-                    let seg_size = (TILE_SIZE / 2) as u32;
-
-                    draw_filled_rect_mut(
-                        &mut tile_image,
-                        Rect::at(0, offset_start).of_size(BORDER_SIZE as u32, seg_size),
-                        Rgb([28, 119, 114]),
-                    );
-                    draw_filled_rect_mut(
-                        &mut tile_image,
-                        Rect::at(offset_start, 0).of_size(seg_size, BORDER_SIZE as u32),
-                        Rgb([28, 119, 114]),
-                    );
-                    draw_filled_rect_mut(
-                        &mut tile_image,
-                        Rect::at(TILE_SIZE - BORDER_SIZE, offset_start)
-                            .of_size(BORDER_SIZE as u32, seg_size),
-                        Rgb([28, 119, 114]),
-                    );
-                    draw_filled_rect_mut(
-                        &mut tile_image,
-                        Rect::at(offset_start, TILE_SIZE - BORDER_SIZE)
-                            .of_size(seg_size, BORDER_SIZE as u32),
-                        Rgb([28, 119, 114]),
-                    );
-                }
+    // in order to properly place the drawn tiles in the image, we need to find the minimum and
+    // maximum x and y values
+    let min_x = flat_grid.iter().map(|tile| tile.x).min().unwrap();
+    let max_x = flat_grid.iter().map(|tile| tile.x).max().unwrap();
+    let min_y = flat_grid.iter().map(|tile| tile.y).min().unwrap();
+    let max_y = flat_grid.iter().map(|tile| tile.y).max().unwrap();
+    let columns = max_x - min_x + 1;
+    let rows = max_y - min_y + 1;
 
-                // Now we draw the inner tile
-                if tile.x == 30 && tile.y == 5 {
-                    info!("{:#?}", tile);
-                }
+    // The top row and left column are left empty for the coordinates
+    let mut full_image = RgbImage::new(
+        ((columns + 1) * TILE_SIZE) as u32,
+        ((rows + 1) * TILE_SIZE) as u32,
+    );
+    let width = full_image.width();
 
-                let color = if tile.occupied {
-                    if tile.faction == *faction {
-                        Rgb([102, 178, 255])
-                    } else {
-                        Rgb([28, 172, 255])
-                    }
-                } else {
-                    Rgb([28, 119, 68])
-                };
-
-                // Draw 4 circles to make the corners of the square
-                draw_filled_circle_mut(
-                    &mut tile_image,
-                    C_1_CENTER,
-                    (IN_TILE_SIZE / 4) as i32,
-                    color,
-                );
-                draw_filled_circle_mut(
-                    &mut tile_image,
-                    C_2_CENTER,
-                    (IN_TILE_SIZE / 4) as i32,
-                    color,
-                );
-                draw_filled_circle_mut(
-                    &mut tile_image,
-                    C_3_CENTER,
-                    (IN_TILE_SIZE / 4) as i32,
-                    color,
-                );
-                draw_filled_circle_mut(
-                    &mut tile_image,
-                    C_4_CENTER,
-                    (IN_TILE_SIZE / 4) as i32,
-                    color,
-                );
+    // North is up, so the first band of tiles under the coordinates is the one with the highest y
+    let mut tile_rows: Vec<Vec<&types::map::Tile>> = vec![Vec::new(); rows as usize];
+    for tile in &flat_grid {
+        tile_rows[(max_y - tile.y) as usize].push(tile);
+    }
+    let band_size = (width * TILE_SIZE as u32 * 3) as usize;
+    let (_, map_area) = full_image.split_at_mut(band_size);
+    let bands: Vec<(&mut [u8], Vec<&types::map::Tile>)> =
+        map_area.chunks_mut(band_size).zip(tile_rows).collect();
 
-                // Draw 2 rectangles to fill the gaps
+    let draw_band = |(band, tiles): (&mut [u8], Vec<&types::map::Tile>)| {
+        let mut band = ImageBuffer::<Rgb<u8>, &mut [u8]>::from_raw(width, TILE_SIZE as u32, band)
+            .expect("Map band is the wrong size");
+        for tile in tiles {
+            let left = ((tile.x - min_x + 1) * TILE_SIZE) as u32;
+            let mut tile_image = band.sub_image(left, 0, TILE_SIZE as u32, TILE_SIZE as u32);
+            let tile_image = &mut *tile_image;
+
+            let visible = tile.faction == faction || visibility.can_see(tile.x, tile.y);
+            let remembered = visibility.remembered.contains_key(&(tile.x, tile.y));
+            // Anything out of sight is covered in fog, and old sightings are faded
+            let shade = if visible {
+                Shade::Visible
+            } else if remembered {
+                Shade::Remembered
+            } else {
+                Shade::Fog
+            };
+            let deposit = terrain.deposit_at(tile.x, tile.y);
+            // The noise is the slowest part of drawing a map, but it only depends on the seed
+            let background = render_cache::background(tile.x, tile.y, shade, || {
+                draw_background(&clamped, &terrain, tile.x, tile.y, shade)
+            });
+            tile_image
+                .copy_from(&background, 0, 0)
+                .expect("Failed to copy tile background");
+
+            if tile.x == center_tile.x && tile.y == center_tile.y {
+                // Draw a little signifier for the centre tile
+                let offset_start = TILE_SIZE / 4;
+                let seg_size = (TILE_SIZE / 2) as u32;
 
                 draw_filled_rect_mut(
-                    &mut tile_image,
-                    Rect::at(INSET_SIZE + (IN_TILE_SIZE / 4), INSET_SIZE)
-                        .of_size((IN_TILE_SIZE / 2) as u32, IN_TILE_SIZE as u32),
-                    color,
+                    tile_image,
+                    Rect::at(0, offset_start).of_size(BORDER_SIZE as u32, seg_size),
+                    Rgb([28, 119, 114]),
                 );
-
                 draw_filled_rect_mut(
-                    &mut tile_image,
-                    Rect::at(INSET_SIZE, INSET_SIZE + (IN_TILE_SIZE / 4))
-                        .of_size(IN_TILE_SIZE as u32, (IN_TILE_SIZE / 2) as u32),
-                    color,
+                    tile_image,
+                    Rect::at(offset_start, 0).of_size(seg_size, BORDER_SIZE as u32),
+                    Rgb([28, 119, 114]),
                 );
+                draw_filled_rect_mut(
+                    tile_image,
+                    Rect::at(TILE_SIZE - BORDER_SIZE, offset_start)
+                        .of_size(BORDER_SIZE as u32, seg_size),
+                    Rgb([28, 119, 114]),
+                );
+                draw_filled_rect_mut(
+                    tile_image,
+                    Rect::at(offset_start, TILE_SIZE - BORDER_SIZE)
+                        .of_size(seg_size, BORDER_SIZE as u32),
+                    Rgb([28, 119, 114]),
+                );
+            }
 
-                // Image is complete, add it to the hashmap
-                completed_tiles.insert((tile.x, tile.y), tile_image);
-                completed.inc();
-            });
-        }
-    });
-
-    let loops_counter = RelaxedCounter::new(0);
-    loop {
-        // we block until every thread is complete. There is probably a better way to do this
-        if completed.get() >= final_amount {
-            // I don't really trust the counter so we'll check again
-            if completed_tiles.len() == final_amount {
-                break;
+            // Unclaimed tiles are left bare so the terrain shows through
+            if !tile.occupied {
+                if visible || remembered {
+                    draw_deposit(tile_image, deposit);
+                }
+                continue;
             }
-        }
-        loops_counter.inc();
-        // Every 300 loops, check if we're done. This is to prevent a deadlock if something goes wrong
-        // with the counter
-        // we are using the same type of counter for this so maybe not the best idea
-        if loops_counter.get() > 300 {
-            loops_counter.reset();
-            if completed_tiles.len() == final_amount {
-                break;
+            let color = if tile.faction == faction {
+                Rgb(OWN_COLOUR)
+            } else {
+                Rgb(colours
+                    .get(&tile.faction)
+                    .copied()
+                    .unwrap_or_else(|| tag_colour(&tile.faction)))
+            };
+
+            // Draw 4 circles to make the corners of the square
+            for centre in [C_1_CENTER, C_2_CENTER, C_3_CENTER, C_4_CENTER] {
+                draw_filled_circle_mut(tile_image, centre, IN_TILE_SIZE / 4, color);
             }
-        }
-    }
 
-    // in order to properly place the drawn tiles in the image, we need to find the minimum x and y values
-
-    let mut x_sorted = flat_grid.iter().map(|tile| tile.x).collect::<Vec<i32>>();
-    x_sorted.sort();
-    let mut y_sorted = flat_grid.iter().map(|tile| tile.y).collect::<Vec<i32>>();
-    y_sorted.sort();
-
-    let min_x = x_sorted[0];
-    let max_x = x_sorted[x_sorted.len() - 1];
-    let min_y = y_sorted[0];
-    let max_y = y_sorted[y_sorted.len() - 1];
-
-    // Figure out which one has the most characters as a string
-    let mut longest_coord_list = vec![min_x, max_x, min_y, max_y];
-    longest_coord_list.sort_by(|a, b| b.to_string().len().cmp(&a.to_string().len()));
-    let longest_coord = longest_coord_list[longest_coord_list.len() - 1];
-
-    for tile in flat_grid {
-        // Grab the image from the hashmap
-        // the cool part is that the order does not matter
-        let tile_image = completed_tiles.get(&(tile.x, tile.y)).unwrap().clone();
-        // Since we know the min x and y values, we can work out where to place it based on the tiles coords
-        // Subtracting the min values gets us the number of "slots" the tile will be from the top left
-        let rel_x = tile.x - min_x;
-        let rel_y = tile.y - min_y;
-        image
-            .copy_from(
-                &tile_image
-                    .view(0, 0, TILE_SIZE as u32, TILE_SIZE as u32)
-                    .to_image(),
-                (rel_x as i32 * (TILE_SIZE)) as u32, // multiply by tile size to get the pixel position
-                (rel_y as i32 * (TILE_SIZE)) as u32,
-            )
-            .expect("Failed to copy tile to final image");
-    }
+            // Draw 2 rectangles to fill the gaps
+            draw_filled_rect_mut(
+                tile_image,
+                Rect::at(INSET_SIZE + (IN_TILE_SIZE / 4), INSET_SIZE)
+                    .of_size((IN_TILE_SIZE / 2) as u32, IN_TILE_SIZE as u32),
+                color,
+            );
+            draw_filled_rect_mut(
+                tile_image,
+                Rect::at(INSET_SIZE, INSET_SIZE + (IN_TILE_SIZE / 4))
+                    .of_size(IN_TILE_SIZE as u32, (IN_TILE_SIZE / 2) as u32),
+                color,
+            );
 
-    let mut img_copy = image.clone();
-    // This is stupid. This is so many levels of stupid. But it's easy. So instead of doing the
-    // smart thing and placing the tiles in the correct order, I'm just going to flip the image
-    // afterwards so I don't have to also flip the perlin noise for each tile. This is terrible and I hate it
-    for y_level in 0..((VIEW_DISTANCE - 1) * TILE_SIZE) {
-        let img_size = image.height() as i32;
-        let inverse_y = img_size - y_level;
-        img_copy
-            .copy_from(
-                &image
-                    .view(0, inverse_y as u32 - 1, image.width(), 1)
-                    .to_image(),
-                0,
-                y_level as u32,
-            )
-            .expect("Failed to copy image layer");
+            draw_faction_borders(tile_image, tile, &owners);
+            draw_contents(tile_image, tile, &ICONS, &font);
+            draw_deposit(tile_image, deposit);
+        }
+    };
+    // Small maps aren't worth handing out to the pool
+    if flat_grid.len() >= PARALLEL_TILES {
+        RENDER_POOL.install(|| bands.into_par_iter().for_each(draw_band));
+    } else {
+        bands.into_iter().for_each(draw_band);
     }
-    image = img_copy;
-
-    let mut full_image = RgbImage::new(
-        image.width() + TILE_SIZE as u32,
-        image.height() + TILE_SIZE as u32,
-    );
 
     let radius = LETTER_WIDTH * 2;
     // Add the coordinates to the image
@@ -277,8 +356,8 @@ pub async fn draw_map(
         draw_filled_circle_mut(
             &mut full_image,
             (
-                (x_coord - min_x) * TILE_SIZE + (TILE_SIZE + (TILE_SIZE / 2)) as i32,
-                (TILE_SIZE / 2) as i32,
+                (x_coord - min_x) * TILE_SIZE + (TILE_SIZE + (TILE_SIZE / 2)),
+                TILE_SIZE / 2,
             ),
             radius,
             Rgb([46, 48, 53]),
@@ -294,18 +373,17 @@ pub async fn draw_map(
         );
     }
 
-    // I need to somehow invert the labels for the Y axis so the lower numbers are at the bottom
-    // TODO
+    // The y labels count down the side, since north is up
     for y_coord in min_y..=max_y {
         let char_len = y_coord.to_string().len() as i32;
-        let mut y = TILE_SIZE + (y_coord - min_x) * TILE_SIZE;
+        let mut y = TILE_SIZE + (max_y - y_coord) * TILE_SIZE;
         y += (TILE_SIZE / 2) - ((scale.y / 2.0) as i32);
         let x = (TILE_SIZE / 2) - (((scale.x / 4.0) as i32) * char_len);
         draw_filled_circle_mut(
             &mut full_image,
             (
-                (TILE_SIZE / 2) as i32,
-                (y_coord - min_y) * TILE_SIZE + (TILE_SIZE + (TILE_SIZE / 2)) as i32,
+                TILE_SIZE / 2,
+                (max_y - y_coord) * TILE_SIZE + (TILE_SIZE + (TILE_SIZE / 2)),
             ),
             radius,
             Rgb([46, 48, 53]),
@@ -324,19 +402,15 @@ pub async fn draw_map(
     // Draw the green border around the map
     draw_filled_rect_mut(
         &mut full_image,
-        Rect::at(TILE_SIZE - (BORDER_SIZE), TILE_SIZE - (BORDER_SIZE * 2)).of_size(
-            (TILE_SIZE * VIEW_DISTANCE - 1) as u32,
-            (BORDER_SIZE * 2) as u32,
-        ),
+        Rect::at(TILE_SIZE - (BORDER_SIZE), TILE_SIZE - (BORDER_SIZE * 2))
+            .of_size((columns * TILE_SIZE - 1) as u32, (BORDER_SIZE * 2) as u32),
         Rgb([28, 119, 68]),
     );
 
     draw_filled_rect_mut(
         &mut full_image,
-        Rect::at(TILE_SIZE - (BORDER_SIZE * 2), TILE_SIZE - (BORDER_SIZE)).of_size(
-            (BORDER_SIZE * 2) as u32,
-            (TILE_SIZE * VIEW_DISTANCE - 1) as u32,
-        ),
+        Rect::at(TILE_SIZE - (BORDER_SIZE * 2), TILE_SIZE - (BORDER_SIZE))
+            .of_size((BORDER_SIZE * 2) as u32, (rows * TILE_SIZE - 1) as u32),
         Rgb([28, 119, 68]),
     );
 
@@ -346,47 +420,789 @@ pub async fn draw_map(
         BORDER_SIZE,
         Rgb([28, 119, 68]),
     );
+    // Label old sightings with how long ago they were made
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let age_scale = Scale {
+        x: AGE_TEXT_SCALE,
+        y: AGE_TEXT_SCALE,
+    };
+    for ((x, y), seen_at) in &visibility.remembered {
+        if *x < min_x || *x > max_x || *y < min_y || *y > max_y {
+            continue;
+        }
+        let left = TILE_SIZE + (x - min_x) * TILE_SIZE;
+        let top = TILE_SIZE + (max_y - y) * TILE_SIZE;
+        let label = format_age(now.saturating_sub(*seen_at));
+        draw_filled_rect_mut(
+            &mut full_image,
+            Rect::at(
+                left + INSET_SIZE,
+                top + TILE_SIZE - INSET_SIZE - AGE_TEXT_SCALE as i32,
+            )
+            .of_size(
+                (label.len() as f32 * AGE_TEXT_SCALE / 2.0) as u32 + 10,
+                AGE_TEXT_SCALE as u32,
+            ),
+            Rgb([46, 48, 53]),
+        );
+        draw_text_mut(
+            &mut full_image,
+            Rgb([255, 255, 255]),
+            left + INSET_SIZE + 5,
+            top + TILE_SIZE - INSET_SIZE - AGE_TEXT_SCALE as i32,
+            age_scale,
+            &font,
+            &label,
+        );
+    }
+
+    // Draw arrows for troop movements on top of everything else, with how many units made it
+    for movement in combine_movements(movements) {
+        let centre = |x: i32, y: i32| {
+            (
+                (TILE_SIZE + (x - min_x) * TILE_SIZE + TILE_SIZE / 2) as f32,
+                (TILE_SIZE + (max_y - y) * TILE_SIZE + TILE_SIZE / 2) as f32,
+            )
+        };
+        let from = centre(movement.from_x, movement.from_y);
+        let to = centre(movement.to_x, movement.to_y);
+        let color = if movement.faction == faction {
+            Rgb([20, 60, 160])
+        } else {
+            Rgb([200, 40, 40])
+        };
+        let (shaft, head) = arrow_shape(from, to);
+        if shaft.len() < 4 {
+            continue;
+        }
+        draw_polygon_mut(&mut full_image, &shaft, color);
+        draw_polygon_mut(&mut full_image, &head, color);
+        let middle = (
+            ((from.0 + to.0) / 2.0) as i32,
+            ((from.1 + to.1) / 2.0) as i32,
+        );
+        let label = movement.amount.to_string();
+        draw_filled_circle_mut(
+            &mut full_image,
+            middle,
+            BADGE_RADIUS + (label.len() as i32 - 1) * 8,
+            color,
+        );
+        draw_text_mut(
+            &mut full_image,
+            Rgb([255, 255, 255]),
+            middle.0 - (label.len() as f32 * AGE_TEXT_SCALE / 4.0) as i32,
+            middle.1 - (AGE_TEXT_SCALE / 2.0) as i32,
+            age_scale,
+            &font,
+            &label,
+        );
+    }
+
+    let mut tags: Vec<String> = owners
+        .values()
+        .filter(|tag| !tag.is_empty())
+        .cloned()
+        .collect();
+    tags.sort_by_key(|tag| (tag != faction, tag.clone()));
+    tags.dedup();
+    full_image = draw_legend(full_image, &tags, faction, colours, &font, TILE_SIZE);
+
+    // Everything is laid out for TILE_SIZE, so smaller tiles are done by shrinking the whole thing
+    let tile_px = map_tile_px(tile_px, columns as u32);
+    if tile_px < TILE_SIZE as u32 {
+        let width = full_image.width() * tile_px / TILE_SIZE as u32;
+        let height = full_image.height() * tile_px / TILE_SIZE as u32;
+        full_image = resize(&full_image, width, height, FilterType::Triangle);
+    }
 
-    full_image
-        .copy_from(&image, TILE_SIZE as u32, TILE_SIZE as u32)
-        .expect("Failed to copy image to final image");
     // And we are done!
     full_image
 }
 
-/// Converts an HSV color to RGB
+// Creates a new image for a tile, coloured by its terrain and textured with noise
+pub(crate) fn draw_background(
+    noise: &Clamp<f64, OpenSimplex, 2>,
+    terrain: &TerrainGenerator,
+    tile_x: i32,
+    tile_y: i32,
+    shade: Shade,
+) -> RgbImage {
+    let mut tile_image = RgbImage::new(TILE_SIZE as u32, TILE_SIZE as u32);
+    let terrain = terrain.at(tile_x, tile_y).data();
+    for x in 0..TILE_SIZE {
+        for y in 0..TILE_SIZE {
+            // Pixels count down from the top of the tile, but y counts up the map
+            let abs_x = (tile_x * TILE_SIZE) + x;
+            let abs_y = (tile_y * TILE_SIZE) + (TILE_SIZE - 1 - y);
+            let value = noise.get([abs_x as f64 / 100.0, abs_y as f64 / 100.0]) as f32;
+            let hsv = match shade {
+                Shade::Visible => hsv_to_rgb(terrain.hue, terrain.saturation, value),
+                Shade::Remembered => hsv_to_rgb(terrain.hue, terrain.saturation * 0.4, value * 0.7),
+                Shade::Fog => hsv_to_rgb(0.0, 0.0, value * 0.5),
+            };
+            tile_image.put_pixel(x as u32, y as u32, Rgb([hsv.0, hsv.1, hsv.2]));
+        }
+    }
+    tile_image
+}
+
+// Draws a line along each edge of a tile that meets another faction's territory. North is up, so
+// the top of the tile is the edge with the tile above it
+fn draw_faction_borders<C: GenericImage<Pixel = Rgb<u8>>>(
+    tile_image: &mut C,
+    tile: &types::map::Tile,
+    owners: &HashMap<(i32, i32), String>,
+) {
+    if tile.faction.is_empty() {
+        return;
+    }
+    let edges = [
+        (
+            (tile.x, tile.y + 1),
+            Rect::at(0, 0).of_size(TILE_SIZE as u32, FACTION_BORDER_SIZE as u32),
+        ),
+        (
+            (tile.x, tile.y - 1),
+            Rect::at(0, TILE_SIZE - FACTION_BORDER_SIZE)
+                .of_size(TILE_SIZE as u32, FACTION_BORDER_SIZE as u32),
+        ),
+        (
+            (tile.x - 1, tile.y),
+            Rect::at(0, 0).of_size(FACTION_BORDER_SIZE as u32, TILE_SIZE as u32),
+        ),
+        (
+            (tile.x + 1, tile.y),
+            Rect::at(TILE_SIZE - FACTION_BORDER_SIZE, 0)
+                .of_size(FACTION_BORDER_SIZE as u32, TILE_SIZE as u32),
+        ),
+    ];
+    for (neighbour, edge) in edges {
+        let owner = owners.get(&neighbour).map(String::as_str).unwrap_or("");
+        if !owner.is_empty() && owner != tile.faction {
+            draw_filled_rect_mut(tile_image, edge, Rgb([20, 20, 20]));
+        }
+    }
+}
+
+// Adds a strip under the map with a colour swatch and tag for every faction on it, wrapping onto
+// more rows if they don't all fit. The entries start `left` pixels in, so they can line up with
+// the map rather than the coordinate gutter
+fn draw_legend(
+    map: RgbImage,
+    tags: &[String],
+    faction: &str,
+    colours: &HashMap<String, [u8; 3]>,
+    font: &Font,
+    left: i32,
+) -> RgbImage {
+    if tags.is_empty() {
+        return map;
+    }
+    let labels: Vec<(String, [u8; 3])> = tags
+        .iter()
+        .map(|tag| {
+            if tag == faction {
+                (format!("{} (you)", tag), OWN_COLOUR)
+            } else {
+                let colour = colours.get(tag).copied().unwrap_or_else(|| tag_colour(tag));
+                (tag.clone(), colour)
+            }
+        })
+        .collect();
+    let positions = legend_layout(&labels, left, map.width() as i32);
+    let rows = positions.iter().map(|(_, y)| y + 1).max().unwrap_or(1);
+    let mut image = RgbImage::new(
+        map.width(),
+        map.height() + (rows * LEGEND_ROW_HEIGHT) as u32,
+    );
+    image
+        .copy_from(&map, 0, 0)
+        .expect("Failed to copy map above legend");
+    let scale = Scale {
+        x: LEGEND_TEXT_SCALE,
+        y: LEGEND_TEXT_SCALE,
+    };
+    for ((label, colour), (x, row)) in labels.iter().zip(positions) {
+        let y = map.height() as i32
+            + row * LEGEND_ROW_HEIGHT
+            + (LEGEND_ROW_HEIGHT - LEGEND_SWATCH_SIZE) / 2;
+        draw_filled_rect_mut(
+            &mut image,
+            Rect::at(x, y).of_size(LEGEND_SWATCH_SIZE as u32, LEGEND_SWATCH_SIZE as u32),
+            Rgb(*colour),
+        );
+        draw_text_mut(
+            &mut image,
+            Rgb([255, 255, 255]),
+            x + LEGEND_SWATCH_SIZE + 10,
+            y,
+            scale,
+            font,
+            label,
+        );
+    }
+    image
+}
+
+/// Works out where each entry in the map legend goes, moving on to a new row whenever the next
+/// entry wouldn't fit
+///
+/// # Arguments
+///
+/// * `labels` - The text of each entry, in order
+/// * `left` - How far in from the left edge each row starts
+/// * `width` - How wide the legend is in pixels
+///
+/// # Returns
+/// ```Vec<(i32, i32)>```: The x position in pixels and the row of each entry
+///
+pub(crate) fn legend_layout<T>(labels: &[(String, T)], left: i32, width: i32) -> Vec<(i32, i32)> {
+    let mut positions = Vec::new();
+    let (mut x, mut row) = (left, 0);
+    for (label, _) in labels {
+        let entry_width =
+            LEGEND_SWATCH_SIZE + 10 + (label.len() as f32 * LEGEND_TEXT_SCALE * 0.45) as i32 + 40;
+        if x + entry_width > width && x > left {
+            x = left;
+            row += 1;
+        }
+        positions.push((x, row));
+        x += entry_width;
+    }
+    positions
+}
+
+/// Marks a tile that has a deposit with a dot in the corner, coloured by the type of deposit
+///
+/// # Arguments
+///
+/// * `tile_image` - The image of the tile to draw on
+/// * `deposit` - The deposit on the tile, if there is one
+///
+fn draw_deposit<C: GenericImage<Pixel = Rgb<u8>>>(tile_image: &mut C, deposit: Option<Deposit>) {
+    if let Some(deposit) = deposit {
+        let center = (
+            TILE_SIZE - DEPOSIT_MARKER_SIZE * 2,
+            TILE_SIZE - DEPOSIT_MARKER_SIZE * 2,
+        );
+        draw_filled_circle_mut(
+            tile_image,
+            center,
+            DEPOSIT_MARKER_SIZE + 2,
+            Rgb([46, 48, 53]),
+        );
+        draw_filled_circle_mut(
+            tile_image,
+            center,
+            DEPOSIT_MARKER_SIZE,
+            Rgb(deposit.data().colour),
+        );
+    }
+}
+
+/// Merges movements that were made together, like each type of unit in an army, so they share a
+/// single arrow with the total number of units on it
+///
+/// # Arguments
+///
+/// * `movements` - The movements to merge
+///
+/// # Returns
+/// ```Vec<Movement>```: One movement for each group, in the order the groups first appear. The
+/// unit is taken from the first movement in the group
+///
+pub(crate) fn combine_movements(movements: &[Movement]) -> Vec<Movement> {
+    let mut combined: Vec<Movement> = Vec::new();
+    let mut groups: HashMap<(String, i32, i32, i32, i32, u64), usize> = HashMap::new();
+    for movement in movements {
+        let key = (
+            movement.faction.clone(),
+            movement.from_x,
+            movement.from_y,
+            movement.to_x,
+            movement.to_y,
+            movement.moved_at,
+        );
+        match groups.get(&key) {
+            Some(index) => combined[*index].amount += movement.amount,
+            None => {
+                groups.insert(key, combined.len());
+                combined.push(movement.clone());
+            }
+        }
+    }
+    combined
+}
+
+/// Works out the outline of an arrow between two points on the map
+///
+/// # Arguments
+///
+/// * `from` - Where the arrow starts
+/// * `to` - Where the arrow points to
+///
+/// # Returns
+/// ```(Vec<Point<i32>>, Vec<Point<i32>>)```: The corners of the shaft and of the head. Both are
+/// empty if the points are too close together to draw an arrow between
+///
+pub(crate) fn arrow_shape(from: (f32, f32), to: (f32, f32)) -> (Vec<Point<i32>>, Vec<Point<i32>>) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length <= ARROW_HEAD_SIZE {
+        return (vec![], vec![]);
+    }
+    // Along the arrow, and across it
+    let (ux, uy) = (dx / length, dy / length);
+    let (nx, ny) = (-uy, ux);
+    let base = (to.0 - ux * ARROW_HEAD_SIZE, to.1 - uy * ARROW_HEAD_SIZE);
+    let point = |x: f32, y: f32| Point::new(x.round() as i32, y.round() as i32);
+    let half = ARROW_WIDTH / 2.0;
+    let shaft = vec![
+        point(from.0 + nx * half, from.1 + ny * half),
+        point(base.0 + nx * half, base.1 + ny * half),
+        point(base.0 - nx * half, base.1 - ny * half),
+        point(from.0 - nx * half, from.1 - ny * half),
+    ];
+    let head = vec![
+        point(to.0, to.1),
+        point(
+            base.0 + nx * ARROW_HEAD_SIZE / 2.0,
+            base.1 + ny * ARROW_HEAD_SIZE / 2.0,
+        ),
+        point(
+            base.0 - nx * ARROW_HEAD_SIZE / 2.0,
+            base.1 - ny * ARROW_HEAD_SIZE / 2.0,
+        ),
+    ];
+    (shaft, head)
+}
+
+// Loads every embedded icon, scaled up to the size they are drawn at
+fn load_icons() -> HashMap<String, RgbaImage> {
+    let mut icons = HashMap::new();
+    for file in MapIcons::iter() {
+        let data = MapIcons::get(&file).unwrap();
+        let icon = image::load_from_memory(&data.data)
+            .expect("Failed to load map icon")
+            .to_rgba8();
+        let icon = resize(
+            &icon,
+            ICON_SIZE as u32,
+            ICON_SIZE as u32,
+            FilterType::Nearest,
+        );
+        icons.insert(file.trim_end_matches(".png").to_string(), icon);
+    }
+    icons
+}
+
+// Blends an icon onto a tile, leaving the tile showing through wherever the icon is see-through
+fn draw_icon<C: GenericImage<Pixel = Rgb<u8>>>(
+    tile_image: &mut C,
+    icon: &RgbaImage,
+    x: i32,
+    y: i32,
+) {
+    for (icon_x, icon_y, pixel) in icon.enumerate_pixels() {
+        let (px, py) = (x + icon_x as i32, y + icon_y as i32);
+        if px < 0 || py < 0 || px >= tile_image.width() as i32 || py >= tile_image.height() as i32 {
+            continue;
+        }
+        let alpha = pixel[3] as f32 / 255.0;
+        let below = tile_image.get_pixel(px as u32, py as u32);
+        let mut blended = [0; 3];
+        for channel in 0..3 {
+            blended[channel] =
+                (pixel[channel] as f32 * alpha + below[channel] as f32 * (1.0 - alpha)) as u8;
+        }
+        tile_image.put_pixel(px as u32, py as u32, Rgb(blended));
+    }
+}
+
+// Writes a count on a dark backing so it can be read on any colour
+fn draw_count<C: GenericImage<Pixel = Rgb<u8>>>(
+    tile_image: &mut C,
+    font: &Font,
+    count: &str,
+    x: i32,
+    y: i32,
+    size: f32,
+) {
+    let scale = Scale { x: size, y: size };
+    draw_filled_rect_mut(
+        tile_image,
+        Rect::at(x, y).of_size((count.len() as f32 * size / 2.0) as u32 + 4, size as u32),
+        Rgb([46, 48, 53]),
+    );
+    draw_text_mut(
+        tile_image,
+        Rgb([255, 255, 255]),
+        x + 2,
+        y,
+        scale,
+        font,
+        count,
+    );
+}
+
+// Draws icons for the buildings on a tile, and a stack with a count for each type of unit on it.
+// Capitals always come first, and the biggest stacks of units are the ones that get shown
+fn draw_contents<C: GenericImage<Pixel = Rgb<u8>>>(
+    tile_image: &mut C,
+    tile: &types::map::Tile,
+    icons: &HashMap<String, RgbaImage>,
+    font: &Font,
+) {
+    let mut buildings: Vec<(&Building, u32)> = tile
+        .buildings
+        .iter()
+        .map(|(building, levels)| (building, levels.total()))
+        .filter(|(_, total)| *total > 0)
+        .collect();
+    buildings.sort_by_key(|(building, _)| (**building != Building::Capital, building.data().name));
+    for (index, (building, total)) in buildings.iter().take(MAX_BUILDING_ICONS).enumerate() {
+        let index = index as i32;
+        let x = INSET_SIZE + (index % BUILDING_ICONS_PER_ROW) * ICON_SIZE;
+        let y = INSET_SIZE + (index / BUILDING_ICONS_PER_ROW) * ICON_SIZE;
+        if let Some(icon) = icons.get(&building.data().name.to_lowercase()) {
+            draw_icon(tile_image, icon, x, y);
+        }
+        if *total > 1 {
+            let count = short_count(*total);
+            let size = COUNT_TEXT_SCALE * 0.7;
+            draw_count(
+                tile_image,
+                font,
+                &count,
+                x + ICON_SIZE - (count.len() as f32 * size / 2.0) as i32 - 4,
+                y + ICON_SIZE - size as i32,
+                size,
+            );
+        }
+    }
+
+    let mut units: Vec<_> = tile
+        .units
+        .iter()
+        .filter(|(_, amount)| **amount > 0)
+        .collect();
+    units.sort_by(|a, b| {
+        b.1.cmp(a.1)
+            .then_with(|| a.0.data().name.cmp(&b.0.data().name))
+    });
+    let stack_width = (TILE_SIZE - INSET_SIZE * 2) / UNIT_STACKS_PER_ROW;
+    let units_top = TILE_SIZE - INSET_SIZE - ICON_SIZE * 2;
+    for (index, (unit, amount)) in units.iter().take(MAX_UNIT_STACKS).enumerate() {
+        let index = index as i32;
+        let x = INSET_SIZE + (index % UNIT_STACKS_PER_ROW) * stack_width;
+        let y = units_top + (index / UNIT_STACKS_PER_ROW) * ICON_SIZE;
+        if let Some(icon) = icons.get(&unit.data().name.to_lowercase()) {
+            draw_icon(tile_image, icon, x, y);
+        }
+        draw_count(
+            tile_image,
+            font,
+            &short_count(**amount),
+            x + ICON_SIZE,
+            y + (ICON_SIZE - COUNT_TEXT_SCALE as i32) / 2,
+            COUNT_TEXT_SCALE,
+        );
+    }
+}
+
+/// Shortens a count so it fits next to an icon, like "950", "1.5k" or "12k"
 ///
 /// # Arguments
 ///
-/// * `h` The hue value, from 0 to 360
-/// * `s` The saturation value, from 0 to 100
-/// * `v` The value value, from 0 to 1
+/// * `count` - The number to shorten
 ///
 /// # Returns
+/// ```String```: The count in at most 4 characters
 ///
-/// * `(u8, u8, u8)` A tuple containing the RGB values
-pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
-    let c = v * (s / 100.0);
-    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
-    let m = v - c;
-
-    let (r, g, b) = if h < 60.0 {
-        (c, x, 0.0)
-    } else if h < 120.0 {
-        (x, c, 0.0)
-    } else if h < 180.0 {
-        (0.0, c, x)
-    } else if h < 240.0 {
-        (0.0, x, c)
-    } else if h < 300.0 {
-        (x, 0.0, c)
+pub(crate) fn short_count(count: u32) -> String {
+    if count < 1_000 {
+        count.to_string()
+    } else if count < 10_000 {
+        format!("{:.1}k", (count / 100) as f32 / 10.0)
+    } else if count < 1_000_000 {
+        format!("{}k", count / 1_000)
     } else {
-        (c, 0.0, x)
+        format!("{}m", count / 1_000_000)
+    }
+}
+
+/// Works out which tiles the overview map covers
+///
+/// # Arguments
+///
+/// * `centre` - The tile in the middle of the map
+/// * `zoom` - How many pixels across each tile is
+///
+/// # Returns
+/// ```((i32, i32), (i32, i32))```: The minimum and maximum x, then the minimum and maximum y
+///
+pub(crate) fn overview_range(centre: (i32, i32), zoom: u32) -> ((i32, i32), (i32, i32)) {
+    let across = OVERVIEW_SIZE / zoom.max(1) as i32;
+    let min_x = centre.0 - across / 2;
+    let min_y = centre.1 - across / 2;
+    ((min_x, min_x + across - 1), (min_y, min_y + across - 1))
+}
+
+/// Draws a zoomed out map where each tile is a small block of colour. Only the terrain, who owns
+/// each tile and where the capitals are is shown, so large areas can be drawn quickly. Even so,
+/// a zoomed out overview covers a lot of tiles, so it is drawn on a thread set aside for blocking
+/// work like draw_map is
+///
+/// # Arguments
+///
+/// * `tiles` - The saved tiles in the area, already hidden by the fog where they can't be seen
+/// * `centre` - The tile in the middle of the map
+/// * `zoom` - How many pixels across each tile is
+/// * `faction` - The faction looking at the map
+/// * `visibility` - What the faction can see and remembers
+/// * `colours` - The colour of each faction on the map
+///
+/// # Returns
+/// ```RgbImage```: The map, with a legend underneath
+///
+pub(crate) async fn draw_overview(
+    tiles: Vec<types::map::Tile>,
+    centre: (i32, i32),
+    zoom: u32,
+    faction: String,
+    visibility: Visibility,
+    colours: HashMap<String, [u8; 3]>,
+) -> RgbImage {
+    tokio::task::spawn_blocking(move || {
+        render_overview(&tiles, centre, zoom, &faction, &visibility, &colours)
+    })
+    .await
+    .expect("Failed to draw overview")
+}
+
+/// Encodes a finished map as a PNG, on a thread set aside for blocking work since big maps take a
+/// while to compress
+///
+/// # Arguments
+///
+/// * `image` - The map to encode
+///
+/// # Returns
+/// ```Vec<u8>```: The PNG file
+///
+pub async fn encode_png(image: RgbImage) -> Vec<u8> {
+    tokio::task::spawn_blocking(move || {
+        let mut cursor = Cursor::new(Vec::new());
+        image
+            .write_to(&mut cursor, ImageOutputFormat::Png)
+            .expect("Failed to encode map");
+        cursor.into_inner()
+    })
+    .await
+    .expect("Failed to encode map")
+}
+
+// Does the actual drawing for draw_overview
+fn render_overview(
+    tiles: &[types::map::Tile],
+    centre: (i32, i32),
+    zoom: u32,
+    faction: &str,
+    visibility: &Visibility,
+    colours: &HashMap<String, [u8; 3]>,
+) -> RgbImage {
+    let zoom = zoom.max(1) as i32;
+    let ((min_x, max_x), (min_y, max_y)) = overview_range(centre, zoom as u32);
+    let size = ((max_x - min_x + 1) * zoom) as u32;
+    let mut image = RgbImage::new(size, size);
+    // North is up, so the highest y is the top row
+    let block = |x: i32, y: i32| {
+        Rect::at((x - min_x) * zoom, (max_y - y) * zoom).of_size(zoom as u32, zoom as u32)
     };
 
-    let r = (r + m) * 255.0;
-    let g = (g + m) * 255.0;
-    let b = (b + m) * 255.0;
+    let terrain = TerrainGenerator::new(get_config().perlin_seed);
+    for x in min_x..=max_x {
+        for y in min_y..=max_y {
+            let data = terrain.at(x, y).data();
+            let (r, g, b) = if visibility.can_see(x, y) {
+                hsv_to_rgb(data.hue, data.saturation * 0.6, 0.35)
+            } else if visibility.remembered.contains_key(&(x, y)) {
+                hsv_to_rgb(data.hue, data.saturation * 0.3, 0.25)
+            } else {
+                hsv_to_rgb(0.0, 0.0, 0.15)
+            };
+            draw_filled_rect_mut(&mut image, block(x, y), Rgb([r, g, b]));
+        }
+    }
+
+    let mut capitals = Vec::new();
+    for tile in tiles {
+        if tile.faction.is_empty()
+            || tile.x < min_x
+            || tile.x > max_x
+            || tile.y < min_y
+            || tile.y > max_y
+        {
+            continue;
+        }
+        let mut colour = if tile.faction == faction {
+            OWN_COLOUR
+        } else {
+            colours
+                .get(&tile.faction)
+                .copied()
+                .unwrap_or_else(|| tag_colour(&tile.faction))
+        };
+        // Old sightings are faded, since they might not be true any more
+        if visibility.remembered.contains_key(&(tile.x, tile.y)) {
+            colour = colour.map(|channel| channel / 2 + 20);
+        }
+        draw_filled_rect_mut(&mut image, block(tile.x, tile.y), Rgb(colour));
+        if tile.buildings.contains_key(&Building::Capital) {
+            capitals.push((tile.x, tile.y));
+        }
+    }
+    // Capitals go on last so neighbouring tiles don't cover them up
+    let marker = (zoom * 2).max(3);
+    for (x, y) in capitals {
+        let centre_x = (x - min_x) * zoom + zoom / 2;
+        let centre_y = (max_y - y) * zoom + zoom / 2;
+        draw_filled_rect_mut(
+            &mut image,
+            Rect::at(centre_x - marker / 2 - 1, centre_y - marker / 2 - 1)
+                .of_size(marker as u32 + 2, marker as u32 + 2),
+            Rgb([20, 20, 20]),
+        );
+        draw_filled_rect_mut(
+            &mut image,
+            Rect::at(centre_x - marker / 2, centre_y - marker / 2)
+                .of_size(marker as u32, marker as u32),
+            Rgb([255, 255, 255]),
+        );
+    }
+
+    let mut tags: Vec<String> = tiles
+        .iter()
+        .filter(|tile| !tile.faction.is_empty())
+        .map(|tile| tile.faction.clone())
+        .collect();
+    tags.sort_by_key(|tag| (tag != faction, tag.clone()));
+    tags.dedup();
+    let font_bytes = Vec::from(include_bytes!("../font.ttf") as &[u8]);
+    let font = Font::try_from_vec(font_bytes).unwrap();
+    draw_legend(image, &tags, faction, colours, &font, LEGEND_MARGIN)
+}
+
+/// Picks which snapshots to use for a timelapse, spread evenly over the whole time so there aren't
+/// too many frames
+///
+/// # Arguments
+///
+/// * `times` - When each snapshot was taken, oldest first
+/// * `max` - The most frames the timelapse can have
+///
+/// # Returns
+/// ```Vec<u64>```: The times of the snapshots to draw, oldest first
+///
+pub(crate) fn pick_frames(times: &[u64], max: usize) -> Vec<u64> {
+    if times.len() <= max {
+        return times.to_vec();
+    }
+    (0..max).map(|i| times[i * times.len() / max]).collect()
+}
+
+/// Draws a map for each moment of a timelapse and puts them together into a looping GIF. Each
+/// frame is labelled in the top corner with how long ago it was
+///
+/// # Arguments
+///
+/// * `frames` - When each frame is from, and the tiles as they were then
+/// * `faction` - The faction looking at the map
+/// * `visibility` - What the faction can see
+/// * `colours` - The colour of each faction on the map
+/// * `tile_px` - How many pixels across each tile is
+/// * `now` - The current epoch time
+///
+/// # Returns
+/// ```Vec<u8>```: The GIF
+///
+pub async fn draw_timelapse(
+    frames: &[(u64, Vec<Vec<types::map::Tile>>)],
+    faction: &str,
+    visibility: &Visibility,
+    colours: &HashMap<String, [u8; 3]>,
+    tile_px: u32,
+    now: u64,
+) -> Vec<u8> {
+    let font_bytes = Vec::from(include_bytes!("../font.ttf") as &[u8]);
+    let font = Font::try_from_vec(font_bytes).unwrap();
+    let mut images = Vec::new();
+    for (taken_at, grid) in frames {
+        let mut image =
+            draw_map(grid, faction.to_string(), visibility, &[], colours, tile_px).await;
+        // The corner above the y coordinates is always empty, so the label goes there
+        let corner = map_tile_px(tile_px, grid.len() as u32) as i32;
+        let size = corner as f32 / 3.0;
+        let age = format_age(now.saturating_sub(*taken_at));
+        let label = if age == "now" {
+            age
+        } else {
+            format!("{} ago", age)
+        };
+        draw_text_mut(
+            &mut image,
+            Rgb([255, 255, 255]),
+            corner / 20,
+            (corner - size as i32) / 2,
+            Scale { x: size, y: size },
+            &font,
+            &label,
+        );
+        images.push(image);
+    }
+
+    // Encoding is slow as well, so it's kept off the bot's threads too
+    tokio::task::spawn_blocking(move || encode_timelapse(images))
+        .await
+        .expect("Failed to encode timelapse")
+}
 
-    (r as u8, g as u8, b as u8)
+// Puts the frames of a timelapse together into a looping GIF
+fn encode_timelapse(images: Vec<RgbImage>) -> Vec<u8> {
+    // Frames can have different sized legends, but a GIF needs every frame to be the same size
+    let width = images.iter().map(|image| image.width()).max().unwrap_or(1);
+    let height = images.iter().map(|image| image.height()).max().unwrap_or(1);
+    let count = images.len();
+    let mut data = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut data, GIF_SPEED);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .expect("Failed to set the timelapse to loop");
+        for (i, image) in images.into_iter().enumerate() {
+            let mut canvas = RgbImage::new(width, height);
+            canvas
+                .copy_from(&image, 0, 0)
+                .expect("Failed to copy timelapse frame");
+            let time = if i + 1 == count {
+                TIMELAPSE_HOLD_TIME
+            } else {
+                TIMELAPSE_FRAME_TIME
+            };
+            let frame = Frame::from_parts(
+                DynamicImage::ImageRgb8(canvas).to_rgba8(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(time, 1),
+            );
+            encoder
+                .encode_frame(frame)
+                .expect("Failed to encode timelapse frame");
+        }
+    }
+    data
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
+use poise::serenity_prelude as serenity;
+
 use crate::{Context, Error};
 
 pub(crate) async fn reply_admin(ctx: Context<'_>) -> Result<bool, Error> {
@@ -41,4 +43,25 @@ pub async fn log_command_used(ctx: Context<'_>) {
         param,
         ctx.author().name
     );
+}
+
+/// Sends someone a DM. People with their DMs closed just don't get told, so failures are only
+/// logged
+///
+/// # Arguments
+///
+/// * `http` - The HTTP client to send the message with
+/// * `user_id` - The ID of the user to message
+/// * `message` - What to tell them
+///
+pub(crate) async fn send_dm(http: &serenity::Http, user_id: &str, message: String) {
+    if let Ok(id) = user_id.parse::<u64>() {
+        let sent = match serenity::UserId(id).create_dm_channel(http).await {
+            Ok(channel) => channel.say(http, message).await.map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            warn!("Couldn't send a DM to {}: {}", id, e);
+        }
+    }
 }
\ No newline at end of file
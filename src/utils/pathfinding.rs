@@ -0,0 +1,146 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+// How far outside the box between the start and the goal a path is allowed to wander. Keeps a
+// search for an unreachable tile from crawling over the whole map
+pub(crate) const SEARCH_MARGIN: i32 = 10;
+// How much longer a diagonal step is than a straight one
+const DIAGONAL_COST: f32 = std::f32::consts::SQRT_2;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Path {
+    pub(crate) tiles: Vec<(i32, i32)>,
+    // Every tile on the path, including the start and the goal
+    pub(crate) cost: f32,
+    // The total movement cost of walking the path
+}
+
+// A tile waiting to be looked at, ordered so the heap hands out the most promising one first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Node {
+    estimate: f32,
+    position: (i32, i32),
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.position.cmp(&other.position))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Works out the cost of stepping from one tile onto a neighbouring one
+///
+/// # Arguments
+///
+/// * `from` - The tile being left
+/// * `to` - The tile being entered
+/// * `terrain_cost` - The movement cost of the terrain on the tile being entered
+///
+/// # Returns
+/// ```f32```: The cost of the step
+///
+pub(crate) fn step_cost(from: (i32, i32), to: (i32, i32), terrain_cost: f32) -> f32 {
+    if from.0 != to.0 && from.1 != to.1 {
+        terrain_cost * DIAGONAL_COST
+    } else {
+        terrain_cost
+    }
+}
+
+// The cheapest a walk between two tiles could possibly be, if it was open ground all the way
+fn heuristic(from: (i32, i32), to: (i32, i32)) -> f32 {
+    let dx = (from.0 - to.0).abs();
+    let dy = (from.1 - to.1).abs();
+    let straight = (dx - dy).abs() as f32;
+    let diagonal = dx.min(dy) as f32;
+    straight + diagonal * DIAGONAL_COST
+}
+
+/// Finds the cheapest path between two tiles with A*. Units can step onto any of the 8 tiles
+/// around them
+///
+/// # Arguments
+///
+/// * `start` - The tile to set off from
+/// * `goal` - The tile to get to
+/// * `terrain_cost` - Gives the movement cost of entering a tile, or None if it can't be entered.
+///   Costs should be at least 1, the cost of open ground
+///
+/// # Returns
+/// ```Option<Path>```: The cheapest path, or None if the goal can't be reached
+///
+pub(crate) fn find_path<F>(start: (i32, i32), goal: (i32, i32), terrain_cost: F) -> Option<Path>
+where
+    F: Fn(i32, i32) -> Option<f32>,
+{
+    let min_x = start.0.min(goal.0) - SEARCH_MARGIN;
+    let max_x = start.0.max(goal.0) + SEARCH_MARGIN;
+    let min_y = start.1.min(goal.1) - SEARCH_MARGIN;
+    let max_y = start.1.max(goal.1) + SEARCH_MARGIN;
+
+    let mut open = BinaryHeap::new();
+    let mut costs: HashMap<(i32, i32), f32> = HashMap::new();
+    let mut came_from: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+    costs.insert(start, 0.0);
+    open.push(Node {
+        estimate: heuristic(start, goal),
+        position: start,
+    });
+
+    while let Some(Node { estimate, position }) = open.pop() {
+        let cost = costs[&position];
+        if position == goal {
+            let mut tiles = vec![goal];
+            let mut current = goal;
+            while let Some(previous) = came_from.get(&current) {
+                tiles.push(*previous);
+                current = *previous;
+            }
+            tiles.reverse();
+            return Some(Path { tiles, cost });
+        }
+        // Already found a cheaper way here since this was queued
+        if estimate > cost + heuristic(position, goal) {
+            continue;
+        }
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = (position.0 + dx, position.1 + dy);
+                if next == position
+                    || next.0 < min_x
+                    || next.0 > max_x
+                    || next.1 < min_y
+                    || next.1 > max_y
+                {
+                    continue;
+                }
+                let terrain = match terrain_cost(next.0, next.1) {
+                    Some(terrain) => terrain,
+                    None => continue,
+                };
+                let next_cost = cost + step_cost(position, next, terrain);
+                if next_cost < *costs.get(&next).unwrap_or(&f32::INFINITY) {
+                    costs.insert(next, next_cost);
+                    came_from.insert(next, position);
+                    open.push(Node {
+                        estimate: next_cost + heuristic(next, goal),
+                        position: next,
+                    });
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use image::RgbImage;
+use lazy_static::lazy_static;
+
+// The world is split into square regions this many tiles across. Writing a tile bumps the revision
+// of its region, which makes every cached map that covers it out of date
+const REGION_SIZE: i32 = 16;
+// Finished maps still show things that change without a tile being written, like how old a
+// sighting is, so they are only kept for this long
+const MAP_LIFETIME: Duration = Duration::from_secs(60);
+const MAX_CACHED_MAPS: usize = 64;
+// Each background is a full TILE_SIZE image, so this caps the cache at around 70MB
+const MAX_CACHED_BACKGROUNDS: usize = 1024;
+
+// How a tile's background is drawn, depending on what the viewer knows about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Shade {
+    Visible,
+    Remembered,
+    Fog,
+}
+
+// Everything that changes how a map comes out, apart from the tiles themselves
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MapKey {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) faction: String,
+    pub(crate) history: Option<u64>,
+    pub(crate) radius: i32,
+    pub(crate) tile_px: u32,
+}
+
+struct CachedMap {
+    revisions: Vec<u64>,
+    // The revision of every region the map depended on when it was drawn
+    drawn_at: Instant,
+    data: Vec<u8>,
+    // The encoded image
+}
+
+lazy_static! {
+    static ref REVISIONS: DashMap<(i32, i32), u64> = DashMap::new();
+    static ref MAPS: DashMap<MapKey, CachedMap> = DashMap::new();
+    static ref BACKGROUNDS: DashMap<(i32, i32, Shade), RgbImage> = DashMap::new();
+}
+
+/// Works out which region a tile is in
+pub(crate) fn region_of(x: i32, y: i32) -> (i32, i32) {
+    (x.div_euclid(REGION_SIZE), y.div_euclid(REGION_SIZE))
+}
+
+/// Marks the region a tile is in as changed, so any map of it gets drawn again
+pub(crate) fn invalidate_tile(x: i32, y: i32) {
+    *REVISIONS.entry(region_of(x, y)).or_insert(0) += 1;
+}
+
+/// Gets the revision of every region that overlaps an area
+///
+/// # Arguments
+///
+/// * `x_range` - The minimum and maximum x values of the area
+/// * `y_range` - The minimum and maximum y values of the area
+///
+/// # Returns
+/// ```Vec<u64>```: The revisions, in the same order every time for the same area
+///
+pub(crate) fn revisions(x_range: (i32, i32), y_range: (i32, i32)) -> Vec<u64> {
+    let (min_x, min_y) = region_of(x_range.0, y_range.0);
+    let (max_x, max_y) = region_of(x_range.1, y_range.1);
+    let mut revisions = Vec::new();
+    for x in min_x..=max_x {
+        for y in min_y..=max_y {
+            revisions.push(REVISIONS.get(&(x, y)).map(|r| *r).unwrap_or(0));
+        }
+    }
+    revisions
+}
+
+/// Gets a finished map, as long as nothing it shows has changed since it was drawn
+///
+/// # Arguments
+///
+/// * `key` - What the map was drawn for
+/// * `revisions` - The current revisions of the regions the map depends on
+///
+/// # Returns
+/// ```Option<Vec<u8>>```: The encoded image, or None if it has to be drawn again
+///
+pub(crate) fn get_map(key: &MapKey, revisions: &[u64]) -> Option<Vec<u8>> {
+    let cached = MAPS.get(key)?;
+    if cached.revisions != revisions || cached.drawn_at.elapsed() > MAP_LIFETIME {
+        return None;
+    }
+    Some(cached.data.clone())
+}
+
+/// Keeps a finished map around so the next request for it doesn't have to draw it again
+///
+/// # Arguments
+///
+/// * `key` - What the map was drawn for
+/// * `revisions` - The revisions of the regions the map depends on, from before it was drawn
+/// * `data` - The encoded image
+///
+pub(crate) fn store_map(key: MapKey, revisions: Vec<u64>, data: Vec<u8>) {
+    if MAPS.len() >= MAX_CACHED_MAPS {
+        MAPS.retain(|_, cached| cached.drawn_at.elapsed() <= MAP_LIFETIME);
+        // Still full of fresh maps, so just start again
+        if MAPS.len() >= MAX_CACHED_MAPS {
+            MAPS.clear();
+        }
+    }
+    MAPS.insert(
+        key,
+        CachedMap {
+            revisions,
+            drawn_at: Instant::now(),
+            data,
+        },
+    );
+}
+
+/// Gets the background of a tile, drawing it if it hasn't been drawn before. Backgrounds only
+/// depend on the map seed, so they never go out of date
+///
+/// # Arguments
+///
+/// * `x` - The x value of the tile
+/// * `y` - The y value of the tile
+/// * `shade` - How the background is shaded
+/// * `draw` - Draws the background if it isn't cached
+///
+/// # Returns
+/// ```RgbImage```: The background
+///
+pub(crate) fn background<F>(x: i32, y: i32, shade: Shade, draw: F) -> RgbImage
+where
+    F: FnOnce() -> RgbImage,
+{
+    if let Some(cached) = BACKGROUNDS.get(&(x, y, shade)) {
+        return cached.clone();
+    }
+    let image = draw();
+    if BACKGROUNDS.len() >= MAX_CACHED_BACKGROUNDS {
+        BACKGROUNDS.clear();
+    }
+    BACKGROUNDS.insert((x, y, shade), image.clone());
+    image
+}
@@ -0,0 +1,105 @@
+use noise::{NoiseFn, OpenSimplex};
+
+use crate::config::get_config;
+use crate::types::terrain::{Deposit, Terrain};
+
+// How many tiles wide the hills and valleys are. Bigger numbers make bigger features
+const ELEVATION_SCALE: f64 = 8.0;
+const MOISTURE_SCALE: f64 = 6.0;
+// Elevation goes from -1 to 1, and these are where each type of terrain starts
+const WATER_LEVEL: f64 = -0.35;
+const HILL_LEVEL: f64 = 0.2;
+const MOUNTAIN_LEVEL: f64 = 0.45;
+// How wet the ground needs to be for trees to grow
+const FOREST_MOISTURE: f64 = 0.15;
+// Deposits are small and rare, so they use tighter noise and only the highest peaks count
+const DEPOSIT_SCALE: f64 = 2.5;
+const DEPOSIT_LEVEL: f64 = 0.45;
+
+/// Works out the terrain of tiles from the map seed. The same seed always gives the same terrain,
+/// so nothing about it needs to be stored
+pub(crate) struct TerrainGenerator {
+    elevation: OpenSimplex,
+    moisture: OpenSimplex,
+    deposits: OpenSimplex,
+}
+
+impl TerrainGenerator {
+    pub(crate) fn new(seed: u32) -> TerrainGenerator {
+        TerrainGenerator {
+            elevation: OpenSimplex::new(seed),
+            moisture: OpenSimplex::new(seed.wrapping_add(1)),
+            deposits: OpenSimplex::new(seed.wrapping_add(2)),
+        }
+    }
+
+    /// Gets the terrain of a tile
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x coordinate of the tile
+    /// * `y` - The y coordinate of the tile
+    ///
+    /// # Returns
+    /// ```Terrain```: The terrain of the tile
+    ///
+    pub(crate) fn at(&self, x: i32, y: i32) -> Terrain {
+        // Sampling from the middle of the tile keeps us off the noise grid, where it is always 0
+        let x = x as f64 + 0.5;
+        let y = y as f64 + 0.5;
+        let elevation = self
+            .elevation
+            .get([x / ELEVATION_SCALE, y / ELEVATION_SCALE]);
+        let moisture = self.moisture.get([x / MOISTURE_SCALE, y / MOISTURE_SCALE]);
+        if elevation < WATER_LEVEL {
+            Terrain::Water
+        } else if elevation > MOUNTAIN_LEVEL {
+            Terrain::Mountains
+        } else if elevation > HILL_LEVEL {
+            Terrain::Hills
+        } else if moisture > FOREST_MOISTURE {
+            Terrain::Forest
+        } else {
+            Terrain::Plains
+        }
+    }
+
+    /// Gets the natural resources on a tile, if there are any. What can be found depends on the
+    /// terrain
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x coordinate of the tile
+    /// * `y` - The y coordinate of the tile
+    ///
+    /// # Returns
+    /// ```Option<Deposit>```: The deposit on the tile
+    ///
+    pub(crate) fn deposit_at(&self, x: i32, y: i32) -> Option<Deposit> {
+        let strength = self.deposits.get([
+            (x as f64 + 0.5) / DEPOSIT_SCALE,
+            (y as f64 + 0.5) / DEPOSIT_SCALE,
+        ]);
+        if strength < DEPOSIT_LEVEL {
+            return None;
+        }
+        match self.at(x, y) {
+            Terrain::Plains => Some(Deposit::FertileSoil),
+            Terrain::Forest => Some(Deposit::Timber),
+            Terrain::Hills => Some(Deposit::Stone),
+            Terrain::Mountains => Some(Deposit::Iron),
+            Terrain::Water => None,
+        }
+    }
+}
+
+/// Gets the terrain of a single tile using the configured seed. Use a TerrainGenerator directly
+/// when looking up lots of tiles
+pub(crate) fn get_terrain(x: i32, y: i32) -> Terrain {
+    TerrainGenerator::new(get_config().perlin_seed).at(x, y)
+}
+
+/// Gets the deposit on a single tile using the configured seed
+pub(crate) fn get_deposit(x: i32, y: i32) -> Option<Deposit> {
+    TerrainGenerator::new(get_config().perlin_seed).deposit_at(x, y)
+}
@@ -1,11 +1,15 @@
 #[path = "types/buildings.rs"]
 pub(crate) mod buildings;
+#[path = "types/diplomacy.rs"]
+pub(crate) mod diplomacy;
 #[path = "types/factions.rs"]
 pub(crate) mod factions;
 #[path = "types/map.rs"]
 pub(crate) mod map;
 #[path = "types/permissions.rs"]
 pub(crate) mod permissions;
+#[path = "types/terrain.rs"]
+pub(crate) mod terrain;
 #[path = "types/units.rs"]
 pub(crate) mod units;
 #[path = "types/users.rs"]
@@ -11,6 +11,109 @@ pub(crate) enum Building {
     Hut,
 }
 
+// The highest level a building can be upgraded to
+pub(crate) const MAX_LEVEL: u32 = 3;
+// The share of a building's cost that is returned when it is torn down
+const REFUND_RATE: f32 = 0.5;
+
+/// How many buildings of one type a tile has at each level. The first entry is level I, the
+/// second is level II and so on
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default, Clone)]
+#[serde(from = "StoredLevels")]
+pub(crate) struct Levels(pub(crate) Vec<u32>);
+
+// Tiles saved before upgrades existed store a plain count, which are all level I buildings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredLevels {
+    Flat(u32),
+    Levels(Vec<u32>),
+}
+
+impl From<StoredLevels> for Levels {
+    fn from(stored: StoredLevels) -> Self {
+        match stored {
+            StoredLevels::Flat(amount) => Levels::new(1, amount),
+            StoredLevels::Levels(levels) => Levels(levels),
+        }
+    }
+}
+
+impl Levels {
+    pub(crate) fn new(level: u32, amount: u32) -> Levels {
+        let mut levels = Levels::default();
+        levels.add(level, amount);
+        levels
+    }
+
+    /// How many buildings there are at a level
+    pub(crate) fn at(&self, level: u32) -> u32 {
+        *self.0.get(level as usize - 1).unwrap_or(&0)
+    }
+
+    /// How many buildings there are across every level
+    pub(crate) fn total(&self) -> u32 {
+        self.0.iter().sum()
+    }
+
+    /// How much the buildings produce together, counting each one by its level's multiplier
+    pub(crate) fn output(&self) -> f64 {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(index, amount)| *amount as f64 * level_multiplier(index as u32 + 1))
+            .sum()
+    }
+
+    pub(crate) fn add(&mut self, level: u32, amount: u32) {
+        if self.0.len() < level as usize {
+            self.0.resize(level as usize, 0);
+        }
+        self.0[level as usize - 1] += amount;
+    }
+
+    /// Takes buildings away, starting with the lowest levels so the best ones are kept
+    pub(crate) fn remove(&mut self, amount: u32) {
+        let mut left = amount;
+        for count in self.0.iter_mut() {
+            let taken = left.min(*count);
+            *count -= taken;
+            left -= taken;
+        }
+    }
+
+    /// Lists the levels that have buildings, like "I: 2, III: 1"
+    pub(crate) fn describe(&self) -> String {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, amount)| **amount > 0)
+            .map(|(index, amount)| format!("{}: {}", level_name(index as u32 + 1), amount))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// How much more a building produces at a level than at level I
+pub(crate) fn level_multiplier(level: u32) -> f64 {
+    1.0 + 0.5 * (level as f64 - 1.0)
+}
+
+/// How many times a building's base cost it takes to upgrade it to a level
+pub(crate) fn upgrade_cost_multiplier(level: u32) -> i32 {
+    level as i32
+}
+
+pub(crate) fn level_name(level: u32) -> String {
+    match level {
+        1 => "I",
+        2 => "II",
+        3 => "III",
+        _ => "?",
+    }
+    .to_string()
+}
+
 #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Default, Clone)]
 pub(crate) struct BuildingData {
     pub(crate) name: String,
@@ -19,13 +122,79 @@ pub(crate) struct BuildingData {
     pub(crate) wood: i32,
     pub(crate) metal: i32,
     pub(crate) space: i32,
+    pub(crate) upkeep: i32,
+    pub(crate) build_time: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct ConstructionJob {
+    #[serde(default)]
+    pub(crate) id: String,
+    // A unique ID for the job
+    #[serde(default)]
+    pub(crate) faction: String,
+    // The tag of the faction constructing the buildings
+    #[serde(default)]
+    pub(crate) x: i32,
+    // The x coordinate of the tile being built on
+    #[serde(default)]
+    pub(crate) y: i32,
+    // The y coordinate of the tile being built on
+    pub(crate) building: Building,
+    // The type of building being constructed
+    #[serde(default)]
+    pub(crate) amount: u32,
+    // How many buildings are being constructed
+    #[serde(default)]
+    pub(crate) started_at: u64,
+    // The epoch time work started. Jobs on the same tile are worked on one after another
+    #[serde(default)]
+    pub(crate) ready_at: u64,
+    // The epoch time the buildings are finished
+    #[serde(default)]
+    pub(crate) ordered_by: String,
+    // The UUID of the user who ordered the construction
+    #[serde(default)]
+    pub(crate) cost: i32,
+    // How much money was paid for the job, so it can be refunded
+    #[serde(default)]
+    pub(crate) wood: i32,
+    // How much wood was paid for the job
+    #[serde(default)]
+    pub(crate) metal: i32,
+    // How much metal was paid for the job
+}
+
+impl ConstructionJob {
+    /// Works out how much of the job's cost is given back if it is cancelled. Work that hasn't
+    /// started yet is refunded in full, and work in progress only gets back the share that hasn't
+    /// been done
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current epoch time
+    ///
+    /// # Returns
+    /// ```f32```: The share of the cost to refund, from 0 to 1
+    ///
+    pub(crate) fn refund_share(&self, now: u64) -> f32 {
+        if now <= self.started_at {
+            return 1.0;
+        }
+        if now >= self.ready_at {
+            return 0.0;
+        }
+        (self.ready_at - now) as f32 / (self.ready_at - self.started_at) as f32
+    }
 }
 
 impl Building {
     pub fn data(&self) -> BuildingData {
         use Building::*;
         // TODO: Balance this shit
-        let (name, description, cost, wood, metal, space) = match self {
+        // Upkeep is how much money the building costs to run each day, and build time is how many
+        // seconds each building takes to construct
+        let (name, description, cost, wood, metal, space, upkeep, build_time) = match self {
             Farm => (
                 "Farm".to_string(),
                 "A farm that produces food".to_string(),
@@ -33,6 +202,8 @@ impl Building {
                 50,
                 0,
                 1,
+                2,
+                300,
             ),
             Mill => (
                 "Mill".to_string(),
@@ -41,6 +212,8 @@ impl Building {
                 50,
                 0,
                 1,
+                2,
+                300,
             ),
             Blacksmith => (
                 "Blacksmith".to_string(),
@@ -49,6 +222,8 @@ impl Building {
                 50,
                 0,
                 5,
+                5,
+                600,
             ),
             Barracks => (
                 "Barracks".to_string(),
@@ -57,6 +232,8 @@ impl Building {
                 800,
                 500,
                 10,
+                10,
+                1800,
             ),
             Capital => (
                 "Capital".to_string(),
@@ -65,6 +242,8 @@ impl Building {
                 0,
                 0,
                 100,
+                0,
+                0,
             ),
 
             House => (
@@ -74,6 +253,8 @@ impl Building {
                 300,
                 50,
                 10,
+                1,
+                900,
             ),
             Hut => (
                 "Hut".to_string(),
@@ -82,6 +263,8 @@ impl Building {
                 100,
                 50,
                 5,
+                0,
+                300,
             ),
         };
         BuildingData {
@@ -91,6 +274,49 @@ impl Building {
             wood,
             metal,
             space,
+            upkeep,
+            build_time,
+        }
+    }
+
+    /// Works out what it costs to build a number of these buildings
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - How many buildings are being built
+    ///
+    /// # Returns
+    /// ```(i32, i32, i32)```: The money, wood and metal needed
+    ///
+    pub fn cost_of(&self, amount: i32) -> (i32, i32, i32) {
+        let details = self.data();
+        (
+            details.cost * amount,
+            details.wood * amount,
+            details.metal * amount,
+        )
+    }
+
+    /// Works out what is salvaged from tearing down a number of these buildings. Only part of what
+    /// they cost is given back
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - How many buildings are being torn down
+    ///
+    /// # Returns
+    /// ```Option<(f32, f32, f32)>```: The money, wood and metal given back, or None if the building
+    /// can't be torn down
+    ///
+    pub fn salvage(&self, amount: u32) -> Option<(f32, f32, f32)> {
+        if *self == Building::Capital {
+            return None;
         }
+        let details = self.data();
+        Some((
+            details.cost as f32 * amount as f32 * REFUND_RATE,
+            details.wood as f32 * amount as f32 * REFUND_RATE,
+            details.metal as f32 * amount as f32 * REFUND_RATE,
+        ))
     }
 }
\ No newline at end of file
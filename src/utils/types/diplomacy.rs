@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq, Default)]
+pub(crate) enum Stance {
+    #[default]
+    Neutral,
+    War,
+    Peace,
+    NonAggression,
+    Alliance,
+}
+
+impl Stance {
+    /// Whether this stance stops the two factions from attacking each other
+    pub fn is_peaceful(&self) -> bool {
+        matches!(self, Stance::Peace | Stance::NonAggression | Stance::Alliance)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Stance::Neutral => "no formal relations",
+            Stance::War => "war",
+            Stance::Peace => "a peace treaty",
+            Stance::NonAggression => "a non-aggression pact",
+            Stance::Alliance => "an alliance",
+        }
+    }
+}
+
+impl fmt::Display for Stance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub(crate) struct Relation {
+    #[serde(default)]
+    pub(crate) faction_a: String,
+    // The tag of the first faction. The tags are always stored in alphabetical order
+    #[serde(default)]
+    pub(crate) faction_b: String,
+    // The tag of the second faction
+    #[serde(default)]
+    pub(crate) stance: Stance,
+    // The current relationship between the two factions
+    #[serde(default)]
+    pub(crate) since: u64,
+    // When the current stance came into effect
+    #[serde(default)]
+    pub(crate) proposal: Option<Stance>,
+    // A stance one of the factions has offered but the other hasn't accepted yet
+    #[serde(default)]
+    pub(crate) proposed_by: String,
+    // The tag of the faction that made the proposal
+}
+
+impl Relation {
+    pub fn new(first: &str, second: &str) -> Relation {
+        let (faction_a, faction_b) = if first < second {
+            (first.to_string(), second.to_string())
+        } else {
+            (second.to_string(), first.to_string())
+        };
+        Relation {
+            faction_a,
+            faction_b,
+            ..Default::default()
+        }
+    }
+
+    /// Gets the tag of the faction on the other side of the relation
+    pub fn other(&self, tag: &str) -> String {
+        if self.faction_a == tag {
+            self.faction_b.clone()
+        } else {
+            self.faction_a.clone()
+        }
+    }
+}
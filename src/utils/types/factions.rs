@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::conversions::hsv_to_rgb;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub(crate) struct Faction {
     #[serde(default)]
@@ -18,6 +20,9 @@ pub(crate) struct Faction {
     pub(crate) leader: String,
     // The leader of the faction
     #[serde(default)]
+    pub(crate) invites: Vec<String>,
+    // The UUIDs of users who have been invited to join the faction
+    #[serde(default)]
     pub(crate) capital_x: i32,
     // The x coordinate of the faction capital
     #[serde(default)]
@@ -25,6 +30,38 @@ pub(crate) struct Faction {
     // The y coordinate of the faction capital
     #[serde(default)]
     pub(crate) production: Production,
+    #[serde(default)]
+    pub(crate) colour: Option<[u8; 3]>,
+    // The colour the faction's territory is drawn in on the map
+}
+
+impl Faction {
+    /// The colour the faction's territory is drawn in. Factions that never picked one get a colour
+    /// worked out from their tag
+    pub(crate) fn colour(&self) -> [u8; 3] {
+        self.colour.unwrap_or_else(|| tag_colour(&self.tag))
+    }
+}
+
+/// Works out a colour from a faction tag, so a faction always comes out the same colour even
+/// if it never picked one
+///
+/// # Arguments
+///
+/// * `tag` - The tag of the faction
+///
+/// # Returns
+/// ```[u8; 3]```: The RGB colour for the faction
+///
+pub(crate) fn tag_colour(tag: &str) -> [u8; 3] {
+    // FNV-1a, so similar tags still end up far apart on the colour wheel
+    let mut hash: u32 = 2166136261;
+    for byte in tag.to_uppercase().bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(16777619);
+    }
+    let (r, g, b) = hsv_to_rgb((hash % 360) as f32, 70.0, 0.9);
+    [r, g, b]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Copy)]
@@ -68,4 +105,13 @@ pub(crate) struct Production {
     #[serde(default)]
     pub(crate) population_cap: i64,
     // The maximum population the faction can have
+    #[serde(default = "default_tax_rate")]
+    pub(crate) tax_rate: f64,
+    // The percentage of their income the faction's people pay in taxes
+}
+
+pub(crate) const DEFAULT_TAX_RATE: f64 = 20.0;
+
+fn default_tax_rate() -> f64 {
+    DEFAULT_TAX_RATE
 }
\ No newline at end of file
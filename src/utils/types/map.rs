@@ -2,15 +2,107 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::types::buildings::Building;
+use crate::terrain::{get_deposit, get_terrain};
+use crate::types::buildings::{Building, Levels};
+use crate::types::terrain::{Deposit, Terrain};
 use crate::types::units::Unit;
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct Tile {
     pub(crate) occupied: bool,
     pub(crate) faction: String,
-    pub(crate) buildings: HashMap<Building, u32>,
+    pub(crate) buildings: HashMap<Building, Levels>,
     pub(crate) units: HashMap<Unit, u32>,
+    #[serde(default)]
+    pub(crate) claimed: bool,
     pub(crate) x: i32,
     pub(crate) y: i32,
+}
+impl Tile {
+    /// Whether the tile is next to another one, including diagonally
+    pub(crate) fn is_adjacent(&self, x: i32, y: i32) -> bool {
+        let distance = ((self.x - x).abs(), (self.y - y).abs());
+        distance != (0, 0) && distance.0 <= 1 && distance.1 <= 1
+    }
+
+    /// The terrain of the tile, which comes from the map seed rather than being stored
+    pub(crate) fn terrain(&self) -> Terrain {
+        get_terrain(self.x, self.y)
+    }
+
+    /// The natural resources on the tile, which also come from the map seed
+    pub(crate) fn deposit(&self) -> Option<Deposit> {
+        get_deposit(self.x, self.y)
+    }
+
+    /// How many of a building there are on the tile, whatever their level
+    pub(crate) fn building_count(&self, building: &Building) -> u32 {
+        self.buildings
+            .get(building)
+            .map(|levels| levels.total())
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn add_buildings(&mut self, building: Building, level: u32, amount: u32) {
+        self.buildings
+            .entry(building)
+            .or_default()
+            .add(level, amount);
+    }
+
+    /// Takes buildings off the tile, lowest levels first
+    pub(crate) fn remove_buildings(&mut self, building: &Building, amount: u32) {
+        if let Some(levels) = self.buildings.get_mut(building) {
+            levels.remove(amount);
+            if levels.total() == 0 {
+                self.buildings.remove(building);
+            }
+        }
+    }
+}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct Intel {
+    #[serde(default)]
+    pub(crate) faction: String,
+    // The tag of the faction that saw the tile
+    #[serde(default)]
+    pub(crate) x: i32,
+    // The x coordinate of the tile
+    #[serde(default)]
+    pub(crate) y: i32,
+    // The y coordinate of the tile
+    pub(crate) tile: Tile,
+    // The tile as the faction saw it, with anything they couldn't make out left off
+    #[serde(default)]
+    pub(crate) seen_at: u64,
+    // The epoch time the faction last saw the tile
+}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct Snapshot {
+    #[serde(default)]
+    pub(crate) x: i32,
+    // The x coordinate of the tile
+    #[serde(default)]
+    pub(crate) y: i32,
+    // The y coordinate of the tile
+    #[serde(default)]
+    pub(crate) faction: String,
+    // The tag of the faction that took the tile, or empty if nobody owns it any more
+    #[serde(default)]
+    pub(crate) taken_at: u64,
+    // The epoch time the snapshot was taken. 0 for who owned the tile before the history starts
+}
+
+impl Snapshot {
+    /// The tile as it was when the snapshot was taken, with only who owned it filled in
+    pub(crate) fn to_tile(&self) -> Tile {
+        Tile {
+            occupied: true,
+            faction: self.faction.clone(),
+            claimed: true,
+            x: self.x,
+            y: self.y,
+            ..Default::default()
+        }
+    }
 }
\ No newline at end of file
@@ -10,6 +10,13 @@ pub(crate) enum Permissions {
     Leader,
 }
 
+impl Permissions {
+    pub fn all() -> Vec<Permissions> {
+        use Permissions::*;
+        vec![NoMoneyCap, MoveTroops, Build, War, Leader]
+    }
+}
+
 impl fmt::Display for Permissions {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(self, f)
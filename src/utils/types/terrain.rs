@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::buildings::Building;
+
+#[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Clone, Copy)]
+pub(crate) enum Terrain {
+    Plains,
+    Forest,
+    Hills,
+    Mountains,
+    Water,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TerrainData {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) movement_cost: f32,
+    pub(crate) passable: bool,
+    pub(crate) hue: f32,
+    pub(crate) saturation: f32,
+}
+
+impl Terrain {
+    pub fn data(&self) -> TerrainData {
+        use Terrain::*;
+        // Movement cost multiplies the food it takes to march onto the tile. Hue and saturation
+        // are the colour of the tile on the map, the noise provides the brightness
+        let (name, description, movement_cost, passable, hue, saturation) = match self {
+            Plains => (
+                "Plains",
+                "Open grassland, good for farming",
+                1.0,
+                true,
+                100.0,
+                70.0,
+            ),
+            Forest => (
+                "Forest",
+                "Thick woods, the only place a mill has anything to cut",
+                1.5,
+                true,
+                143.0,
+                96.0,
+            ),
+            Hills => (
+                "Hills",
+                "Rolling hills that slow down marching troops",
+                1.5,
+                true,
+                60.0,
+                60.0,
+            ),
+            Mountains => (
+                "Mountains",
+                "Rocky peaks where nothing grows",
+                2.5,
+                true,
+                30.0,
+                15.0,
+            ),
+            Water => (
+                "Water",
+                "Lakes and seas that can't be crossed or built on",
+                1.0,
+                false,
+                210.0,
+                80.0,
+            ),
+        };
+        TerrainData {
+            name: name.to_string(),
+            description: description.to_string(),
+            movement_cost,
+            passable,
+            hue,
+            saturation,
+        }
+    }
+
+    /// Whether a building can be built on this terrain
+    pub fn allows(&self, building: &Building) -> bool {
+        use Terrain::*;
+        match self {
+            Water => false,
+            Mountains => !matches!(building, Building::Farm | Building::Mill),
+            Forest => true,
+            Plains | Hills => *building != Building::Mill,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Clone, Copy)]
+pub(crate) enum Deposit {
+    Iron,
+    Timber,
+    FertileSoil,
+    Stone,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DepositData {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) multiplier: f64,
+    pub(crate) colour: [u8; 3],
+}
+
+impl Deposit {
+    pub fn data(&self) -> DepositData {
+        use Deposit::*;
+        // The multiplier applies to the output of every matching building on the tile
+        let (name, description, multiplier, colour) = match self {
+            Iron => (
+                "Iron",
+                "A seam of iron ore that doubles the output of blacksmiths",
+                2.0,
+                [90, 90, 110],
+            ),
+            Timber => (
+                "Timber",
+                "Old, tall trees that double the output of mills",
+                2.0,
+                [120, 80, 40],
+            ),
+            FertileSoil => (
+                "Fertile soil",
+                "Rich soil that doubles the output of farms",
+                2.0,
+                [230, 200, 60],
+            ),
+            Stone => (
+                "Stone",
+                "Good building stone that lets houses and huts hold twice as many people",
+                2.0,
+                [200, 200, 200],
+            ),
+        };
+        DepositData {
+            name: name.to_string(),
+            description: description.to_string(),
+            multiplier,
+            colour,
+        }
+    }
+
+    /// Whether the deposit boosts a type of building
+    pub fn boosts(&self, building: &Building) -> bool {
+        use Deposit::*;
+        match self {
+            Iron => *building == Building::Blacksmith,
+            Timber => *building == Building::Mill,
+            FertileSoil => *building == Building::Farm,
+            Stone => matches!(building, Building::House | Building::Hut),
+        }
+    }
+}
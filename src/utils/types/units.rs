@@ -1,5 +1,11 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::pathfinding::step_cost;
+use crate::terrain::TerrainGenerator;
+use crate::types::factions::Production;
+
 #[derive(Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Clone)]
 pub(crate) enum Unit {
     Citizen,
@@ -15,30 +21,362 @@ pub(crate) struct UnitData {
     pub(crate) name: String,
     pub(crate) description: String,
     pub(crate) space: i32,
+    pub(crate) strength: i32,
     pub(crate) beats: Vec<Unit>,
+    pub(crate) cost: i32,
+    pub(crate) metal: i32,
+    pub(crate) food: i32,
+    pub(crate) train_time: u64,
+    pub(crate) food_upkeep: i32,
+    pub(crate) money_upkeep: i32,
+    pub(crate) march_time: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct TrainingOrder {
+    #[serde(default)]
+    pub(crate) id: String,
+    // A unique ID for the order
+    #[serde(default)]
+    pub(crate) faction: String,
+    // The tag of the faction training the units
+    #[serde(default)]
+    pub(crate) x: i32,
+    // The x coordinate of the tile the units will appear on
+    #[serde(default)]
+    pub(crate) y: i32,
+    // The y coordinate of the tile the units will appear on
+    pub(crate) unit: Unit,
+    // The type of unit being trained
+    #[serde(default)]
+    pub(crate) amount: u32,
+    // How many units are being trained
+    #[serde(default)]
+    pub(crate) ready_at: u64,
+    // The epoch time the units finish training
+    #[serde(default)]
+    pub(crate) ordered_by: String,
+    // The UUID of the user who ordered the training
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub(crate) struct Army {
+    #[serde(default)]
+    pub(crate) id: String,
+    // A unique ID for the army
+    #[serde(default)]
+    pub(crate) faction: String,
+    // The tag of the faction the army marches for
+    #[serde(default)]
+    pub(crate) units: HashMap<Unit, u32>,
+    // The units in the army
+    #[serde(default)]
+    pub(crate) route: Vec<(i32, i32)>,
+    // Every tile the army passes through, from where it set off to where it's going
+    #[serde(default)]
+    pub(crate) step: u32,
+    // How far along the route the army has got
+    #[serde(default)]
+    pub(crate) next_step_at: u64,
+    // The epoch time the army reaches the next tile on its route
+    #[serde(default)]
+    pub(crate) arrives_at: u64,
+    // The epoch time the army is expected to reach the end of its route
+    #[serde(default)]
+    pub(crate) returning: bool,
+    // Whether the army has given up and is heading back to where it came from
+    #[serde(default)]
+    pub(crate) ordered_by: String,
+    // The UUID of the user who sent the army
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct Movement {
+    #[serde(default)]
+    pub(crate) faction: String,
+    // The tag of the faction that moved the units
+    #[serde(default)]
+    pub(crate) from_x: i32,
+    // The x coordinate of the tile the units set off from
+    #[serde(default)]
+    pub(crate) from_y: i32,
+    // The y coordinate of the tile the units set off from
+    #[serde(default)]
+    pub(crate) to_x: i32,
+    // The x coordinate of the tile the units ended up on
+    #[serde(default)]
+    pub(crate) to_y: i32,
+    // The y coordinate of the tile the units ended up on
+    pub(crate) unit: Unit,
+    // The type of unit that moved
+    #[serde(default)]
+    pub(crate) amount: u32,
+    // How many of them made it
+    #[serde(default)]
+    pub(crate) moved_at: u64,
+    // The epoch time the units arrived
+}
+
+impl Army {
+    /// The tile the army is currently on
+    pub(crate) fn position(&self) -> (i32, i32) {
+        self.route[self.step as usize]
+    }
+
+    /// The tile the army was on before its last step
+    pub(crate) fn previous(&self) -> (i32, i32) {
+        self.route[self.step.saturating_sub(1) as usize]
+    }
+
+    pub(crate) fn destination(&self) -> (i32, i32) {
+        self.route[self.route.len() - 1]
+    }
+
+    pub(crate) fn arrived(&self) -> bool {
+        self.step as usize + 1 >= self.route.len()
+    }
+
+    /// How many seconds the army takes to cross a tile of open ground. Armies move at the pace of
+    /// their slowest unit
+    pub(crate) fn march_time(&self) -> u64 {
+        self.units
+            .keys()
+            .map(|unit| unit.data().march_time)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// How many seconds the army will take to reach the next tile on its route
+    ///
+    /// # Arguments
+    ///
+    /// * `terrain` - The generator for the map's terrain
+    ///
+    /// # Returns
+    /// ```u64```: The time the next step takes, or 0 if the army has arrived
+    ///
+    pub(crate) fn step_time(&self, terrain: &TerrainGenerator) -> u64 {
+        if self.arrived() {
+            return 0;
+        }
+        let from = self.position();
+        let to = self.route[self.step as usize + 1];
+        let cost = step_cost(from, to, terrain.at(to.0, to.1).data().movement_cost);
+        (self.march_time() as f32 * cost).ceil() as u64
+    }
+
+    /// How many seconds the army will take to walk the rest of its route, starting from now
+    pub(crate) fn remaining_time(&self, terrain: &TerrainGenerator) -> u64 {
+        let mut ahead = self.clone();
+        let mut total = 0;
+        while !ahead.arrived() {
+            total += ahead.step_time(terrain);
+            ahead.step += 1;
+        }
+        total
+    }
+
+    /// Whether two armies ran into each other on their last steps, either by ending up on the
+    /// same tile or by passing each other going opposite ways
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The army that might have been met
+    ///
+    /// # Returns
+    /// ```bool```: Whether the armies met
+    ///
+    pub(crate) fn meets(&self, other: &Army) -> bool {
+        if self.position() == other.position() {
+            return true;
+        }
+        self.position() == other.previous()
+            && self.previous() == other.position()
+            && self.step > 0
+            && other.step > 0
+    }
+
+    /// Turns the army around so it marches back the way it came
+    pub(crate) fn turn_back(&mut self) {
+        let mut route = self.route[..=self.step as usize].to_vec();
+        route.reverse();
+        self.route = route;
+        self.step = 0;
+        self.returning = true;
+    }
 }
 
 impl Unit {
     pub fn data(&self) -> UnitData {
         use Unit::*;
-        let (name, description, space, beats) = match self {
-            Citizen => ("Citizen", "A normal citizen of the faction", 1, vec![]),
-            Soldier => ("Soldier", "A regular soldier", 1, vec![Citizen]),
-            Cavalry => ("Cavalry", "A fast moving cavalry unit", 1, vec![Soldier]),
-            Ranger => ("Ranger", "A long range unit", 1, vec![Soldier, Cavalry]),
+        // Train time is in seconds per unit, per barracks. Upkeep is how much food and money each
+        // unit costs to keep fed and paid each day. March time is how many seconds the unit takes to
+        // cross a tile of open ground
+        let (
+            name,
+            description,
+            space,
+            strength,
+            beats,
+            cost,
+            metal,
+            food,
+            train_time,
+            food_upkeep,
+            money_upkeep,
+            march_time,
+        ) = match self {
+            Citizen => (
+                "Citizen",
+                "A normal citizen of the faction",
+                1,
+                0,
+                vec![],
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                60,
+            ),
+            Soldier => (
+                "Soldier",
+                "A regular soldier",
+                1,
+                2,
+                vec![Citizen],
+                10,
+                5,
+                10,
+                60,
+                1,
+                1,
+                60,
+            ),
+            Cavalry => (
+                "Cavalry",
+                "A fast moving cavalry unit",
+                1,
+                3,
+                vec![Soldier],
+                30,
+                10,
+                30,
+                180,
+                2,
+                3,
+                30,
+            ),
+            Ranger => (
+                "Ranger",
+                "A long range unit",
+                1,
+                3,
+                vec![Soldier, Cavalry],
+                40,
+                15,
+                20,
+                240,
+                1,
+                3,
+                60,
+            ),
             Knight => (
                 "Knight",
                 "A heavy hitting knight",
                 1,
+                5,
                 vec![Cavalry, Soldier],
+                60,
+                40,
+                30,
+                300,
+                2,
+                5,
+                120,
+            ),
+            Scout => (
+                "Scout",
+                "A fast moving scout",
+                1,
+                1,
+                vec![Citizen],
+                15,
+                0,
+                10,
+                90,
+                1,
+                1,
+                20,
             ),
-            Scout => ("Scout", "A fast moving scout", 1, vec![Citizen]),
         };
         UnitData {
             name: name.to_string(),
             description: description.to_string(),
             space,
+            strength,
             beats: beats.to_vec(),
+            cost,
+            metal,
+            food,
+            train_time,
+            food_upkeep,
+            money_upkeep,
+            march_time,
+        }
+    }
+
+    /// Works out what it costs to train a number of these units
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - How many units are being trained
+    ///
+    /// # Returns
+    /// ```(f32, f32, f32)```: The money, metal and food needed
+    ///
+    pub fn training_cost(&self, amount: u32) -> (f32, f32, f32) {
+        let details = self.data();
+        (
+            details.cost as f32 * amount as f32,
+            details.metal as f32 * amount as f32,
+            details.food as f32 * amount as f32,
+        )
+    }
+
+    /// Checks that a faction has everything it needs to train a number of these units. Every unit
+    /// is someone taken out of the general population, so there have to be enough people as well
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - How many units are being trained
+    /// * `production` - What the faction has
+    ///
+    /// # Returns
+    /// ```Option<String>```: The reason the units can't be trained, or None if they can
+    ///
+    pub fn refuse_training(&self, amount: u32, production: &Production) -> Option<String> {
+        let (cost, metal, food) = self.training_cost(amount);
+        if cost > production.money {
+            Some(format!(
+                "You don't have enough money to train that many! You need ${:.2}.",
+                cost
+            ))
+        } else if metal > production.metal {
+            Some(format!(
+                "You don't have enough metal to train that many! You need {}.",
+                metal
+            ))
+        } else if food > production.food {
+            Some(format!(
+                "You don't have enough food to train that many! You need {}kg.",
+                food
+            ))
+        } else if amount as f64 > production.population.floor() {
+            Some("You don't have enough people to train that many units!".to_string())
+        } else {
+            None
         }
     }
 }
\ No newline at end of file
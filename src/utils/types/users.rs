@@ -1,6 +1,9 @@
 use crate::types::permissions::Permissions;
 use serde::{Deserialize, Serialize};
 
+// The share of the treasury a member without NoMoneyCap can spend on a single order
+const SPENDING_CAP: f32 = 0.25;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub(crate) struct User {
     #[serde(default)]
@@ -19,4 +22,35 @@ impl User {
     pub fn permitted(&self, permission: Permissions) -> bool {
         self.permissions.contains(&permission) || self.permissions.contains(&Permissions::Leader)
     }
+
+    /// The most money this user can spend from the treasury on a single order
+    pub fn spending_limit(&self, treasury: f32) -> f32 {
+        if self.permitted(Permissions::NoMoneyCap) {
+            treasury
+        } else {
+            treasury * SPENDING_CAP
+        }
+    }
+
+    /// Checks that this user is allowed to spend an amount from the treasury on a single order
+    ///
+    /// # Arguments
+    ///
+    /// * `cost` - How much money the order costs
+    /// * `treasury` - How much money the faction has
+    ///
+    /// # Returns
+    /// ```Option<String>```: The reason the order was refused, or None if it is allowed
+    ///
+    pub fn refuse_spending(&self, cost: f32, treasury: f32) -> Option<String> {
+        let limit = self.spending_limit(treasury);
+        if cost > limit {
+            Some(format!(
+                "You can only spend ${:.2} of the treasury at once without the NoMoneyCap permission!",
+                limit
+            ))
+        } else {
+            None
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::types::map::Tile;
+use crate::types::units::{Movement, Unit};
+
+// How many tiles away a faction can see from each tile it owns
+pub const SIGHT_RANGE: i32 = 4;
+// How many tiles away a faction can make out enemy troops from each tile it owns
+pub const UNIT_SIGHT_RANGE: i32 = 1;
+// Scouts see further than anyone else, and can count enemy troops as far as they can see
+pub const SCOUT_SIGHT_RANGE: i32 = 8;
+
+/// What a faction can currently see of the map
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Visibility {
+    // Tiles the faction can see the owner, terrain and buildings of
+    pub(crate) seen: HashSet<(i32, i32)>,
+    // Tiles the faction can also see enemy units on
+    pub(crate) scouted: HashSet<(i32, i32)>,
+    // Tiles out of sight that are being shown as they were last seen, and when that was
+    pub(crate) remembered: HashMap<(i32, i32), u64>,
+}
+
+impl Visibility {
+    /// Works out what can be seen from a faction's territory
+    ///
+    /// # Arguments
+    ///
+    /// * `territory` - Every tile the faction owns
+    ///
+    /// # Returns
+    /// ```Visibility```: The tiles the faction can see
+    ///
+    pub(crate) fn from_territory(territory: &[Tile]) -> Visibility {
+        let mut visibility = Visibility::default();
+        for tile in territory {
+            let has_scouts = tile.units.get(&Unit::Scout).unwrap_or(&0) > &0;
+            let (sight, unit_sight) = if has_scouts {
+                (SCOUT_SIGHT_RANGE, SCOUT_SIGHT_RANGE)
+            } else {
+                (SIGHT_RANGE, UNIT_SIGHT_RANGE)
+            };
+            for x in tile.x - sight..=tile.x + sight {
+                for y in tile.y - sight..=tile.y + sight {
+                    visibility.seen.insert((x, y));
+                    if (x - tile.x).abs() <= unit_sight && (y - tile.y).abs() <= unit_sight {
+                        visibility.scouted.insert((x, y));
+                    }
+                }
+            }
+        }
+        visibility
+    }
+
+    pub(crate) fn can_see(&self, x: i32, y: i32) -> bool {
+        self.seen.contains(&(x, y))
+    }
+
+    pub(crate) fn can_scout(&self, x: i32, y: i32) -> bool {
+        self.scouted.contains(&(x, y))
+    }
+
+    /// Hides whatever the faction can't see on a tile. Tiles out of sight come back blank, and
+    /// enemy units are removed unless the tile has been scouted
+    ///
+    /// # Arguments
+    ///
+    /// * `tile` - The tile as it really is
+    /// * `faction` - The faction looking at the tile
+    ///
+    /// # Returns
+    /// ```Tile```: The tile as the faction sees it
+    ///
+    pub(crate) fn mask(&self, tile: Tile, faction: &str) -> Tile {
+        if tile.faction == faction {
+            return tile;
+        }
+        if !self.can_see(tile.x, tile.y) {
+            return Tile {
+                x: tile.x,
+                y: tile.y,
+                ..Default::default()
+            };
+        }
+        let mut tile = tile;
+        if !self.can_scout(tile.x, tile.y) {
+            tile.units.clear();
+        }
+        tile
+    }
+
+    /// Picks out the troop movements a faction knows about. A faction always knows where its own
+    /// troops went, and sees anyone else's arrive on tiles it can see
+    ///
+    /// # Arguments
+    ///
+    /// * `movements` - Every movement that might be shown
+    /// * `faction` - The faction looking at the map
+    ///
+    /// # Returns
+    /// ```Vec<Movement>```: The movements the faction knows about
+    ///
+    pub(crate) fn known_movements(&self, movements: Vec<Movement>, faction: &str) -> Vec<Movement> {
+        movements
+            .into_iter()
+            .filter(|movement| {
+                movement.faction == faction || self.can_see(movement.to_x, movement.to_y)
+            })
+            .collect()
+    }
+}
+
+/// Describes how long ago something was seen in as few characters as possible, like "5m" or "3d",
+/// so it fits on a map tile
+///
+/// # Arguments
+///
+/// * `seconds` - How many seconds ago it was seen
+///
+/// # Returns
+/// ```String```: The short description
+///
+pub(crate) fn format_age(seconds: u64) -> String {
+    if seconds < 60 {
+        "now".to_string()
+    } else if seconds < 60 * 60 {
+        format!("{}m", seconds / 60)
+    } else if seconds < 60 * 60 * 24 {
+        format!("{}h", seconds / (60 * 60))
+    } else {
+        format!("{}d", seconds / (60 * 60 * 24))
+    }
+}